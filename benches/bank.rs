@@ -0,0 +1,37 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use pid::{PidBank, PidController};
+
+fn errors(n: usize) -> Vec<f64> {
+    (0..n).map(|c| (c as f64 * 0.37).sin()).collect()
+}
+
+fn bank_against_controllers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step_all");
+    for &n in &[4, 12, 64, 256] {
+        let errors = errors(n);
+        let mut outputs = vec![0.0; n];
+
+        let mut bank = PidBank::new(n, 0.001, (-1.0, 1.0)).unwrap();
+        bank.set_gains_all(1.2, 0.4, 0.01).unwrap();
+        bank.set_smooth_all(0.5).unwrap();
+        group.bench_with_input(BenchmarkId::new("PidBank", n), &n, |b, _| {
+            b.iter(|| bank.step_all(black_box(&errors), &mut outputs).unwrap())
+        });
+
+        let mut pid = PidController::new(0.001, (-1.0, 1.0));
+        pid.set_gains(1.2, 0.4, 0.01).unwrap();
+        pid.set_smooth(0.5).unwrap();
+        let mut pids = vec![pid; n];
+        group.bench_with_input(BenchmarkId::new("PidController", n), &n, |b, _| {
+            b.iter(|| {
+                for ((pid, &e), u) in pids.iter_mut().zip(black_box(&errors)).zip(&mut outputs) {
+                    *u = pid.step(e);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bank_against_controllers);
+criterion_main!(benches);
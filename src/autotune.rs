@@ -0,0 +1,546 @@
+use crate::{
+    check_clamp, check_dt, check_threshold, PidController, PidError, PidGains, TuningRule,
+};
+
+use num_traits::{Float, FloatConst};
+
+/// relay feedback experiment (Astrom-Hagglund) estimating the ultimate gain
+/// and period of a plant.
+///
+/// step() is driven like PidController::update(): feed it measurements and
+/// apply what it returns to the plant. The output switches between the two
+/// relay levels whenever the measurement crosses the setpoint by more than
+/// the hysteresis. Each cycle, from one switch to the high level to the
+/// next, gives a period and an amplitude. Once `cycles` consecutive cycles
+/// agree within the tolerance, their averages give tu and
+/// ku = 4 d / (pi sqrt(a^2 - eps^2)), with d half the relay step, a half the
+/// peak-to-peak measurement and eps the hysteresis. Cycles with an
+/// amplitude within the hysteresis are not counted.
+///
+/// ```
+/// use pid::{RelayTuner, TuningRule};
+///
+/// // three first-order lags 1 / (s + 1)^3: ku = 8, tu = 2 pi / sqrt(3)
+/// let dt = 0.01;
+/// let mut tuner = RelayTuner::<f64>::new(dt, 0.0, (-1.0, 1.0)).unwrap();
+/// let mut x = [0.0; 3];
+/// while tuner.is_running() {
+///     let u = tuner.step(x[2]);
+///     x[0] += dt * (u - x[0]);
+///     x[1] += dt * (x[0] - x[1]);
+///     x[2] += dt * (x[1] - x[2]);
+/// }
+///
+/// let result = tuner.result().unwrap();
+/// assert!((result.ku - 8.0).abs() < 1.0);
+/// assert!((result.tu - 3.63).abs() < 0.2);
+/// let gains = result.gains(TuningRule::ClassicPid).unwrap();
+/// assert!((gains.kp - 0.6 * result.ku).abs() < 1e-12);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RelayTuner<T = f64> {
+    dt: T,
+    setpoint: T,
+    low: T,
+    high: T,
+    hysteresis: T,
+
+    /// relative spread allowed between consecutive cycles
+    tolerance: T,
+
+    /// consecutive agreeing cycles averaged into the result
+    cycles: usize,
+
+    /// cycles after which the experiment fails
+    max_cycles: usize,
+
+    /// rule update() turns the result into gains with
+    rule: TuningRule,
+
+    status: TuningStatus,
+    relay_high: bool,
+
+    /// time since the last switch to the high level, None before the first
+    since_rise: Option<T>,
+    y_min: T,
+    y_max: T,
+
+    /// period and amplitude of the previous cycle
+    last_cycle: Option<(T, T)>,
+    completed: usize,
+    agreeing: usize,
+    period_sum: T,
+    amplitude_sum: T,
+}
+
+/// progress of a RelayTuner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TuningStatus {
+    /// still switching the relay
+    Running,
+
+    /// the oscillation settled, result() is available
+    Converged,
+
+    /// no steady oscillation within max_cycles
+    Failed,
+}
+
+/// what RelayTuner::update() returns each step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutoTuneState<T = f64> {
+    /// the experiment is running, apply this relay output to the plant
+    InProgress(T),
+
+    /// converged, gains from the tuner's rule, see set_rule()
+    Done(PidGains<T>),
+
+    /// no steady oscillation within max_cycles
+    Failed,
+}
+
+/// ultimate gain and period found by a relay experiment
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TuningResult<T = f64> {
+    /// ultimate gain
+    pub ku: T,
+
+    /// ultimate period
+    pub tu: T,
+
+    /// half the peak-to-peak measurement during the oscillation
+    pub amplitude: T,
+}
+
+impl<T: Float + FloatConst> RelayTuner<T> {
+    /// dt: time between step() calls
+    /// setpoint: measurement the relay oscillates around
+    /// levels: (low, high) relay output. for a relay of amplitude d around
+    /// a bias u0, pass (u0 - d, u0 + d).
+    pub fn new(dt: T, setpoint: T, levels: (T, T)) -> Result<Self, PidError> {
+        let dt = check_dt(dt)?;
+        if setpoint.is_nan() {
+            return Err(PidError::NaN);
+        }
+        let (low, high) = check_clamp(levels.0, levels.1)?;
+        Ok(Self {
+            dt,
+            setpoint,
+            low,
+            high,
+            hysteresis: T::zero(),
+            tolerance: T::from(0.05).unwrap(),
+            cycles: 3,
+            max_cycles: 20,
+            rule: TuningRule::ClassicPid,
+            status: TuningStatus::Running,
+            relay_high: true,
+            since_rise: None,
+            y_min: T::infinity(),
+            y_max: T::neg_infinity(),
+            last_cycle: None,
+            completed: 0,
+            agreeing: 0,
+            period_sum: T::zero(),
+            amplitude_sum: T::zero(),
+        })
+    }
+
+    /// band around the setpoint the measurement must leave before the relay
+    /// switches, to ride through measurement noise. 0 by default.
+    /// result() corrects ku for it, but it also slows the oscillation below
+    /// the ultimate frequency, so keep it just above the noise level.
+    pub fn set_hysteresis(&mut self, hysteresis: T) -> Result<(), PidError> {
+        self.hysteresis = check_threshold(hysteresis)?;
+        Ok(())
+    }
+
+    /// relative difference in period and amplitude between consecutive
+    /// cycles that still counts as a steady oscillation. 0.05 by default.
+    pub fn set_tolerance(&mut self, tolerance: T) -> Result<(), PidError> {
+        self.tolerance = check_threshold(tolerance)?;
+        Ok(())
+    }
+
+    /// number of consecutive agreeing cycles averaged into the result,
+    /// at least 1. 3 by default.
+    pub fn set_cycles(&mut self, cycles: usize) {
+        self.cycles = cycles.max(1);
+    }
+
+    /// completed cycles after which the experiment gives up. 20 by default.
+    pub fn set_max_cycles(&mut self, max_cycles: usize) {
+        self.max_cycles = max_cycles;
+    }
+
+    /// tuning rule the gains of update() follow. TuningRule::ClassicPid by
+    /// default.
+    pub fn set_rule(&mut self, rule: TuningRule) {
+        self.rule = rule;
+    }
+
+    /// y: measurement
+    /// returns the relay output while running, and the midpoint of the
+    /// relay levels once the experiment has converged or failed.
+    pub fn step(&mut self, y: T) -> T {
+        if self.status != TuningStatus::Running {
+            return (self.low + self.high) / (T::one() + T::one());
+        }
+
+        if let Some(t) = self.since_rise.as_mut() {
+            *t = *t + self.dt;
+            self.y_min = self.y_min.min(y);
+            self.y_max = self.y_max.max(y);
+        }
+
+        let e = self.setpoint - y;
+        if self.relay_high && e < -self.hysteresis {
+            self.relay_high = false;
+        } else if !self.relay_high && e > self.hysteresis {
+            self.relay_high = true;
+            self.finish_cycle();
+        }
+
+        if self.relay_high {
+            self.high
+        } else {
+            self.low
+        }
+    }
+
+    /// called on every switch to the high level
+    fn finish_cycle(&mut self) {
+        let two = T::one() + T::one();
+        if let Some(period) = self.since_rise {
+            let amplitude = (self.y_max - self.y_min) / two;
+            self.completed += 1;
+
+            let agrees = match self.last_cycle {
+                Some((p, a)) => {
+                    (period - p).abs() <= self.tolerance * period
+                        && (amplitude - a).abs() <= self.tolerance * amplitude
+                }
+                None => false,
+            };
+            if agrees && amplitude > self.hysteresis {
+                self.agreeing += 1;
+                self.period_sum = self.period_sum + period;
+                self.amplitude_sum = self.amplitude_sum + amplitude;
+            } else {
+                self.agreeing = 0;
+                self.period_sum = T::zero();
+                self.amplitude_sum = T::zero();
+            }
+            self.last_cycle = Some((period, amplitude));
+
+            if self.agreeing >= self.cycles {
+                self.status = TuningStatus::Converged;
+            } else if self.completed >= self.max_cycles {
+                self.status = TuningStatus::Failed;
+            }
+        }
+
+        self.since_rise = Some(T::zero());
+        self.y_min = T::infinity();
+        self.y_max = T::neg_infinity();
+    }
+
+    /// step() that hands out the gains once the experiment is over.
+    ///
+    /// ```
+    /// use pid::{AutoTuneState, RelayTuner};
+    ///
+    /// // relay of amplitude 1 around 0 on three lags 1 / (s + 1)^3
+    /// let dt = 0.01;
+    /// let mut tuner = RelayTuner::<f64>::new(dt, 0.0, (-1.0, 1.0)).unwrap();
+    /// let mut x = [0.0; 3];
+    /// let gains = loop {
+    ///     match tuner.update(x[2]) {
+    ///         AutoTuneState::InProgress(u) => {
+    ///             x[0] += dt * (u - x[0]);
+    ///             x[1] += dt * (x[0] - x[1]);
+    ///             x[2] += dt * (x[1] - x[2]);
+    ///         }
+    ///         AutoTuneState::Done(gains) => break gains,
+    ///         AutoTuneState::Failed => panic!("no steady oscillation"),
+    ///     }
+    /// };
+    /// assert!((gains.kp - 0.6 * 8.0).abs() < 0.6);
+    /// ```
+    pub fn update(&mut self, y: T) -> AutoTuneState<T> {
+        if self.is_running() {
+            let u = self.step(y);
+            if self.is_running() {
+                return AutoTuneState::InProgress(u);
+            }
+        }
+        match self.result().map(|result| result.gains(self.rule)) {
+            Some(Ok(gains)) => AutoTuneState::Done(gains),
+            _ => AutoTuneState::Failed,
+        }
+    }
+
+    pub fn status(&self) -> TuningStatus {
+        self.status
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status == TuningStatus::Running
+    }
+
+    /// runs the whole experiment against a simulated or sampled plant.
+    ///
+    /// y: measurement before the first step
+    /// max_steps: step() calls after which the experiment is abandoned, so a
+    /// plant that never crosses the setpoint cannot hang the loop
+    /// plant: applies the relay output for one dt, returns the new
+    /// measurement
+    ///
+    /// returns result(), None if the experiment failed or ran out of steps.
+    ///
+    /// ```
+    /// use pid::RelayTuner;
+    ///
+    /// // first order plus dead time e^-s / (s + 1): ku = 2.26, tu = 3.10
+    /// let dt = 0.01;
+    /// let mut delay = [0.0; 100];
+    /// let mut k = 0;
+    /// let mut y = 0.0;
+    /// let plant = |u: f64| {
+    ///     let delayed = core::mem::replace(&mut delay[k], u);
+    ///     k = (k + 1) % delay.len();
+    ///     y += dt * (delayed - y);
+    ///     y
+    /// };
+    ///
+    /// let mut tuner = RelayTuner::new(dt, 0.0, (-1.0, 1.0)).unwrap();
+    /// let result = tuner.run(0.0, 100_000, plant).unwrap();
+    /// assert!((result.ku - 2.26).abs() < 0.15 * 2.26);
+    /// assert!((result.tu - 3.10).abs() < 0.05 * 3.10);
+    /// ```
+    pub fn run<F>(&mut self, y: T, max_steps: usize, mut plant: F) -> Option<TuningResult<T>>
+    where
+        F: FnMut(T) -> T,
+    {
+        let mut y = y;
+        for _ in 0..max_steps {
+            if !self.is_running() {
+                break;
+            }
+            y = plant(self.step(y));
+        }
+        self.result()
+    }
+
+    /// the estimate, once the experiment has converged
+    pub fn result(&self) -> Option<TuningResult<T>> {
+        if self.status != TuningStatus::Converged {
+            return None;
+        }
+        let n = T::from(self.agreeing).unwrap();
+        let amplitude = self.amplitude_sum / n;
+        let d = (self.high - self.low) / (T::one() + T::one());
+        // describing function of a relay with hysteresis
+        let a = (amplitude.powi(2) - self.hysteresis.powi(2)).sqrt();
+        Some(TuningResult {
+            ku: (T::one() + T::one()).powi(2) * d / (T::PI() * a),
+            tu: self.period_sum / n,
+            amplitude,
+        })
+    }
+
+    /// restarts the experiment, keeping the configuration
+    pub fn reset(&mut self) {
+        *self = Self {
+            hysteresis: self.hysteresis,
+            tolerance: self.tolerance,
+            cycles: self.cycles,
+            max_cycles: self.max_cycles,
+            rule: self.rule,
+            ..Self::new(self.dt, self.setpoint, (self.low, self.high)).unwrap()
+        };
+    }
+}
+
+impl<T: Float> TuningResult<T> {
+    /// parallel-form gains for the given rule
+    pub fn gains(&self, rule: TuningRule) -> Result<PidGains<T>, PidError> {
+        let (kp, ti, td) = rule.standard_gains(self.ku, self.tu)?;
+        Ok(PidGains {
+            kp,
+            ki: kp / ti,
+            kd: kp * td,
+        })
+    }
+
+    /// sets the controller's gains from the given rule, see
+    /// set_standard_gains()
+    pub fn apply_to(&self, pid: &mut PidController<T>, rule: TuningRule) -> Result<(), PidError> {
+        let (kp, ti, td) = rule.standard_gains(self.ku, self.tu)?;
+        pid.set_standard_gains(kp, ti, td)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// three first-order lags 1 / (s + 1)^3 with measurement noise in
+    /// [-noise, noise): ku = 8, tu = 2 pi / sqrt(3)
+    fn tune(hysteresis: f64, noise: f64) -> TuningResult<f64> {
+        let dt = 0.001;
+        let mut tuner = RelayTuner::<f64>::new(dt, 0.0, (-1.0, 1.0)).unwrap();
+        tuner.set_hysteresis(hysteresis).unwrap();
+        let mut x = [0.0; 3];
+        let mut seed = 5u64;
+        let mut steps = 0;
+        while tuner.is_running() && steps < 1_000_000 {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let n = noise * ((seed >> 11) as f64 / (1u64 << 52) as f64 - 1.0);
+            let u = tuner.step(x[2] + n);
+            x[0] += dt * (u - x[0]);
+            x[1] += dt * (x[0] - x[1]);
+            x[2] += dt * (x[1] - x[2]);
+            steps += 1;
+        }
+        tuner.result().unwrap()
+    }
+
+    /// two first-order lags 1 / (s + 1)^2, one explicit Euler step
+    fn second_order(x: &mut [f64; 2], u: f64, dt: f64) -> f64 {
+        x[0] += dt * (u - x[0]);
+        x[1] += dt * (x[0] - x[1]);
+        x[1]
+    }
+
+    #[test]
+    fn tuned_gains_close_a_damped_loop_on_a_second_order_plant() {
+        let dt = 0.001;
+        let mut tuner = RelayTuner::<f64>::new(dt, 0.0, (-1.0, 1.0)).unwrap();
+        // the plant lags less than 180 degrees, so it oscillates through the
+        // hysteresis
+        tuner.set_hysteresis(0.05).unwrap();
+        let mut x = [0.0; 2];
+        let mut y = 0.0;
+        while tuner.is_running() {
+            y = second_order(&mut x, tuner.step(y), dt);
+        }
+        let result = tuner.result().unwrap();
+
+        for (rule, max_overshoot) in [
+            (TuningRule::ClassicPid, 0.5),
+            (TuningRule::TyreusLuyben, 0.15),
+        ] {
+            let mut pid = PidController::new(dt, (-10.0, 10.0));
+            result.apply_to(&mut pid, rule).unwrap();
+            pid.set_derivative_mode(crate::DerivativeMode::OnMeasurement);
+            let mut x = [0.0; 2];
+            let mut y = 0.0;
+            let mut peak: f64 = 0.0;
+            for _ in 0..20_000 {
+                y = second_order(&mut x, pid.step_with_setpoint(1.0, y), dt);
+                assert!(y.abs() < 2.0, "{:?} diverges", rule);
+                peak = peak.max(y);
+            }
+            assert!(peak - 1.0 < max_overshoot, "{:?}: {}", rule, peak);
+            assert!((y - 1.0).abs() < 1e-3, "{:?}: {}", rule, y);
+        }
+    }
+
+    #[test]
+    fn update_hands_out_the_gains_of_the_rule() {
+        let dt = 0.001;
+        let mut tuner = RelayTuner::<f64>::new(dt, 0.0, (-1.0, 1.0)).unwrap();
+        tuner.set_rule(TuningRule::TyreusLuyben);
+        tuner.set_cycles(2);
+        let mut x = [0.0; 3];
+        let gains = loop {
+            match tuner.update(x[2]) {
+                AutoTuneState::InProgress(u) => {
+                    assert!(u == 1.0 || u == -1.0);
+                    x[0] += dt * (u - x[0]);
+                    x[1] += dt * (x[0] - x[1]);
+                    x[2] += dt * (x[1] - x[2]);
+                }
+                AutoTuneState::Done(gains) => break gains,
+                AutoTuneState::Failed => panic!("{:?}", tuner.status()),
+            }
+        };
+        let result = tuner.result().unwrap();
+        assert_eq!(gains, result.gains(TuningRule::TyreusLuyben).unwrap());
+        assert_eq!(tuner.update(0.0), AutoTuneState::Done(gains));
+    }
+
+    #[test]
+    fn update_reports_a_failed_experiment() {
+        let mut tuner = RelayTuner::<f64>::new(0.1, 0.0, (-1.0, 1.0)).unwrap();
+        tuner.set_max_cycles(3);
+        // a measurement jumping at random never settles into one period
+        let mut seed = 1u64;
+        let mut state = AutoTuneState::InProgress(0.0);
+        for _ in 0..1000 {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let y = if seed >> 63 == 0 { 1.0 } else { -1.0 };
+            state = tuner.update(y);
+            if state == AutoTuneState::Failed {
+                break;
+            }
+        }
+        assert_eq!(state, AutoTuneState::Failed);
+        assert_eq!(tuner.status(), TuningStatus::Failed);
+    }
+
+    #[test]
+    fn finds_the_ultimate_point_without_noise() {
+        let result = tune(0.0, 0.0);
+        assert!((result.ku - 8.0).abs() < 0.1 * 8.0, "{:?}", result);
+        assert!((result.tu - 3.63).abs() < 0.05 * 3.63, "{:?}", result);
+    }
+
+    #[test]
+    fn run_gives_up_on_a_plant_that_never_crosses() {
+        let mut tuner = RelayTuner::<f64>::new(0.01, 0.0, (-1.0, 1.0)).unwrap();
+        let mut calls = 0;
+        let result = tuner.run(5.0, 1_000, |_| {
+            calls += 1;
+            5.0
+        });
+        assert_eq!(result, None);
+        assert_eq!(calls, 1_000);
+        assert!(tuner.is_running());
+    }
+
+    /// the gain that puts the real part of the plant at -1 at the
+    /// oscillation frequency, what the describing function of a relay with
+    /// hysteresis estimates
+    fn real_part_gain(tu: f64) -> f64 {
+        let w = 2.0 * core::f64::consts::PI / tu;
+        -(1.0 + w * w).powf(1.5) / (3.0 * w.atan()).cos()
+    }
+
+    fn uncorrected(result: &TuningResult<f64>) -> f64 {
+        4.0 / (core::f64::consts::PI * result.amplitude)
+    }
+
+    #[test]
+    fn corrects_for_the_hysteresis() {
+        let result = tune(0.1, 0.0);
+        let gain = real_part_gain(result.tu);
+        assert!((result.ku - gain).abs() < 0.02 * gain, "{:?}", result);
+        assert!((uncorrected(&result) - gain).abs() > 0.05 * gain);
+    }
+
+    #[test]
+    fn hysteresis_rides_through_noise() {
+        let result = tune(0.1, 0.02);
+        let gain = real_part_gain(result.tu);
+        assert!((result.ku - gain).abs() < 0.1 * gain, "{:?}", result);
+        assert!((result.ku - gain).abs() < (uncorrected(&result) - gain).abs());
+    }
+}
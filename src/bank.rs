@@ -0,0 +1,263 @@
+use crate::{check_clamp, check_dt, check_gain, check_smooth, PidError, PidGains, Saturation};
+
+use alloc::vec;
+use alloc::vec::Vec;
+use num_traits::Float;
+
+/// many identical loops stepped together, with gains and state stored per
+/// field across channels (struct of arrays).
+///
+/// each channel behaves like a PidController in its default configuration
+/// with its own gains, clamp and smoothing: step() on the error and
+/// conditional integration anti-windup. other PidController options are
+/// not available.
+///
+/// ```
+/// use pid::{PidBank, PidController};
+///
+/// let mut bank = PidBank::<f64>::new(3, 0.1, (-1.0, 1.0)).unwrap();
+/// bank.set_gains_all(0.5, 2.0, 0.1).unwrap();
+/// bank.set_gains(2, 1.0, 0.0, 0.0).unwrap();
+///
+/// let mut pid = PidController::<f64>::new(0.1, (-1.0, 1.0));
+/// pid.set_gains(0.5, 2.0, 0.1).unwrap();
+///
+/// let mut outputs = [0.0; 3];
+/// for k in 0..50 {
+///     let e = 1.0 - 0.05 * k as f64;
+///     bank.step_all(&[e, e, e], &mut outputs).unwrap();
+///     assert_eq!(outputs[0], pid.step(e));
+///     assert_eq!(outputs[2], e.min(1.0).max(-1.0));
+/// }
+/// assert!(bank.step_all(&[0.0; 2], &mut outputs).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PidBank<T = f64> {
+    dt: T,
+    kp: Vec<T>,
+    ki: Vec<T>,
+    kd: Vec<T>,
+    clamp_lo: Vec<T>,
+    clamp_hi: Vec<T>,
+    smooth: Vec<T>,
+
+    /// accumulated error per channel
+    i: Vec<T>,
+
+    /// smoothed derivative per channel
+    d: Vec<T>,
+    e_prev: Vec<T>,
+    saturation: Vec<Saturation>,
+}
+
+impl<T: Float> PidBank<T> {
+    /// channels loops sharing dt, all clamped to clamp and with zero gains
+    pub fn new(channels: usize, dt: T, clamp: (T, T)) -> Result<Self, PidError> {
+        let dt = check_dt(dt)?;
+        let (lo, hi) = check_clamp(clamp.0, clamp.1)?;
+        Ok(Self {
+            dt,
+            kp: vec![T::zero(); channels],
+            ki: vec![T::zero(); channels],
+            kd: vec![T::zero(); channels],
+            clamp_lo: vec![lo; channels],
+            clamp_hi: vec![hi; channels],
+            smooth: vec![T::one(); channels],
+            i: vec![T::zero(); channels],
+            d: vec![T::zero(); channels],
+            e_prev: vec![T::zero(); channels],
+            saturation: vec![Saturation::None; channels],
+        })
+    }
+
+    pub fn channels(&self) -> usize {
+        self.kp.len()
+    }
+
+    pub fn dt(&self) -> T {
+        self.dt
+    }
+
+    fn check_channel(&self, channel: usize) -> Result<(), PidError> {
+        if channel < self.channels() {
+            Ok(())
+        } else {
+            Err(PidError::ChannelOutOfRange)
+        }
+    }
+
+    pub fn set_gains(&mut self, channel: usize, kp: T, ki: T, kd: T) -> Result<(), PidError> {
+        self.check_channel(channel)?;
+        let (kp, ki, kd) = (check_gain(kp)?, check_gain(ki)?, check_gain(kd)?);
+        self.kp[channel] = kp;
+        self.ki[channel] = ki;
+        self.kd[channel] = kd;
+        Ok(())
+    }
+
+    /// set_gains() on every channel
+    pub fn set_gains_all(&mut self, kp: T, ki: T, kd: T) -> Result<(), PidError> {
+        let (kp, ki, kd) = (check_gain(kp)?, check_gain(ki)?, check_gain(kd)?);
+        self.kp.iter_mut().for_each(|g| *g = kp);
+        self.ki.iter_mut().for_each(|g| *g = ki);
+        self.kd.iter_mut().for_each(|g| *g = kd);
+        Ok(())
+    }
+
+    pub fn set_clamp(&mut self, channel: usize, clamp: (T, T)) -> Result<(), PidError> {
+        self.check_channel(channel)?;
+        let (lo, hi) = check_clamp(clamp.0, clamp.1)?;
+        self.clamp_lo[channel] = lo;
+        self.clamp_hi[channel] = hi;
+        Ok(())
+    }
+
+    pub fn set_smooth(&mut self, channel: usize, smooth: T) -> Result<(), PidError> {
+        self.check_channel(channel)?;
+        self.smooth[channel] = check_smooth(smooth)?;
+        Ok(())
+    }
+
+    /// set_smooth() on every channel
+    pub fn set_smooth_all(&mut self, smooth: T) -> Result<(), PidError> {
+        let smooth = check_smooth(smooth)?;
+        self.smooth.iter_mut().for_each(|s| *s = smooth);
+        Ok(())
+    }
+
+    /// gains of a channel
+    pub fn gains(&self, channel: usize) -> Option<PidGains<T>> {
+        Some(PidGains {
+            kp: *self.kp.get(channel)?,
+            ki: self.ki[channel],
+            kd: self.kd[channel],
+        })
+    }
+
+    /// (lo, hi) clamp of a channel
+    pub fn clamp(&self, channel: usize) -> Option<(T, T)> {
+        Some((*self.clamp_lo.get(channel)?, self.clamp_hi[channel]))
+    }
+
+    /// steps every channel with its error. both slices must have one entry
+    /// per channel, otherwise nothing is stepped.
+    pub fn step_all(&mut self, errors: &[T], outputs: &mut [T]) -> Result<(), PidError> {
+        let n = self.channels();
+        if errors.len() != n || outputs.len() != n {
+            return Err(PidError::LengthMismatch);
+        }
+        let dt = self.dt;
+
+        for c in 0..n {
+            let e = errors[c];
+            let de = e - self.e_prev[c];
+            self.e_prev[c] = e;
+
+            let integrate = match self.saturation[c] {
+                Saturation::None => true,
+                Saturation::Upper => e <= T::zero(),
+                Saturation::Lower => e >= T::zero(),
+            };
+            if integrate {
+                self.i[c] = self.i[c] + dt * e;
+            }
+            let (lo, hi) = (self.clamp_lo[c], self.clamp_hi[c]);
+            let ki = self.ki[c];
+
+            let smooth = self.smooth[c];
+            self.d[c] = smooth * de / dt + (T::one() - smooth) * self.d[c];
+
+            let u = self.kp[c] * e + ki * self.i[c] + self.kd[c] * self.d[c];
+            let total = u.max(lo).min(hi);
+            self.saturation[c] = if u >= hi {
+                Saturation::Upper
+            } else if u <= lo {
+                Saturation::Lower
+            } else {
+                Saturation::None
+            };
+            outputs[c] = total;
+        }
+        Ok(())
+    }
+
+    /// clears the state of every channel, keeping gains and clamps
+    pub fn reset(&mut self) {
+        self.i.iter_mut().for_each(|x| *x = T::zero());
+        self.d.iter_mut().for_each(|x| *x = T::zero());
+        self.e_prev.iter_mut().for_each(|x| *x = T::zero());
+        self.saturation
+            .iter_mut()
+            .for_each(|s| *s = Saturation::None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PidController;
+
+    #[test]
+    fn every_channel_matches_a_standalone_controller() {
+        let channels = 12;
+        let mut bank = PidBank::<f64>::new(channels, 0.01, (-1.0, 1.0)).unwrap();
+        let mut pids = Vec::new();
+        for c in 0..channels {
+            let x = c as f64;
+            let (kp, ki, kd) = (0.5 + 0.2 * x, 0.1 * x, 0.01 * (x % 4.0));
+            let clamp = (-0.5 - 0.1 * x, 0.2 + 0.3 * x);
+            let smooth = 1.0 - 0.07 * x;
+            bank.set_gains(c, kp, ki, kd).unwrap();
+            bank.set_clamp(c, clamp).unwrap();
+            bank.set_smooth(c, smooth).unwrap();
+
+            let mut pid = PidController::new(0.01, clamp);
+            pid.set_gains(kp, ki, kd).unwrap();
+            pid.set_smooth(smooth).unwrap();
+            pids.push(pid);
+        }
+
+        let mut errors = vec![0.0; channels];
+        let mut outputs = vec![0.0; channels];
+        let mut saturated = 0;
+        for k in 0..2000 {
+            for (c, e) in errors.iter_mut().enumerate() {
+                *e = 3.0 * (k as f64 * 0.01 * (1.0 + c as f64)).sin();
+            }
+            bank.step_all(&errors, &mut outputs).unwrap();
+            for c in 0..channels {
+                assert_eq!(outputs[c], pids[c].step(errors[c]), "channel {}", c);
+                saturated += pids[c].is_saturated() as usize;
+            }
+        }
+        assert!(saturated > 0);
+
+        bank.reset();
+        for pid in &mut pids {
+            pid.reset();
+        }
+        bank.step_all(&errors, &mut outputs).unwrap();
+        for c in 0..channels {
+            assert_eq!(outputs[c], pids[c].step(errors[c]));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_channels_and_lengths() {
+        let mut bank = PidBank::<f64>::new(2, 0.1, (-1.0, 1.0)).unwrap();
+        assert_eq!(
+            bank.set_gains(2, 1.0, 0.0, 0.0),
+            Err(PidError::ChannelOutOfRange)
+        );
+        assert_eq!(bank.gains(2), None);
+        let mut outputs = [0.0; 2];
+        assert_eq!(
+            bank.step_all(&[0.0; 3], &mut outputs),
+            Err(PidError::LengthMismatch)
+        );
+        assert_eq!(
+            bank.step_all(&[0.0; 2], &mut [0.0; 1]),
+            Err(PidError::LengthMismatch)
+        );
+    }
+}
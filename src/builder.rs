@@ -0,0 +1,238 @@
+use crate::{ControllerDirection, DerivativeMode, PidController, PidError};
+
+use num_traits::Float;
+
+/// validated construction of a PidController
+///
+/// PidController::builder().dt(0.01).kp(1.2).ki(0.4).clamp(-10.0, 10.0).build()
+///
+/// unset parameters default to dt = 1, zero gains, no clamping, no smoothing.
+/// values are only checked by build(). settings the builder does not offer
+/// are set on the built controller.
+#[derive(Debug, Clone)]
+pub struct PidControllerBuilder<T = f64> {
+    dt: T,
+    kp: T,
+    ki: T,
+    kd: T,
+    clamp_lo: T,
+    clamp_hi: T,
+    smooth: T,
+    derivative_mode: DerivativeMode,
+    direction: ControllerDirection,
+}
+
+impl<T: Float> Default for PidControllerBuilder<T> {
+    fn default() -> Self {
+        Self {
+            dt: T::one(),
+            kp: T::zero(),
+            ki: T::zero(),
+            kd: T::zero(),
+            clamp_lo: T::neg_infinity(),
+            clamp_hi: T::infinity(),
+            smooth: T::one(),
+            derivative_mode: DerivativeMode::OnError,
+            direction: ControllerDirection::Direct,
+        }
+    }
+}
+
+impl<T: Float> PidControllerBuilder<T> {
+    /// starts from the same arguments as PidController::new
+    pub fn new(dt: T, clamp: (T, T)) -> Self {
+        Self::default().dt(dt).clamp(clamp.0, clamp.1)
+    }
+
+    pub fn dt(mut self, dt: T) -> Self {
+        self.dt = dt;
+        self
+    }
+
+    pub fn kp(mut self, kp: T) -> Self {
+        self.kp = kp;
+        self
+    }
+
+    pub fn ki(mut self, ki: T) -> Self {
+        self.ki = ki;
+        self
+    }
+
+    pub fn kd(mut self, kd: T) -> Self {
+        self.kd = kd;
+        self
+    }
+
+    pub fn clamp(mut self, lo: T, hi: T) -> Self {
+        self.clamp_lo = lo;
+        self.clamp_hi = hi;
+        self
+    }
+
+    pub fn smooth(mut self, smooth: T) -> Self {
+        self.smooth = smooth;
+        self
+    }
+
+    pub fn derivative_mode(mut self, mode: DerivativeMode) -> Self {
+        self.derivative_mode = mode;
+        self
+    }
+
+    pub fn direction(mut self, direction: ControllerDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// fails instead of adjusting anything: NaN, dt <= 0, clamp_lo >= clamp_hi,
+    /// a negative or infinite gain or smooth outside [0,1] each return the
+    /// matching PidError.
+    pub fn build(self) -> Result<PidController<T>, PidError> {
+        let mut pid = PidController::try_new(self.dt, (self.clamp_lo, self.clamp_hi))?;
+        pid.set_kp(self.kp)?;
+        pid.set_ki(self.ki)?;
+        pid.set_kd(self.kd)?;
+        pid.set_smooth(self.smooth)?;
+        pid.set_derivative_mode(self.derivative_mode);
+        pid.set_direction(self.direction);
+        Ok(pid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> PidControllerBuilder<f64> {
+        PidControllerBuilder::new(0.1, (-1.0, 1.0))
+    }
+
+    #[test]
+    fn new_takes_the_arguments_of_the_controller() {
+        let pid = builder().build().unwrap();
+        let reference = PidController::<f64>::new(0.1, (-1.0, 1.0));
+        assert_eq!(pid.dt(), reference.dt());
+        assert_eq!(
+            (pid.clamp_lo(), pid.clamp_hi()),
+            (reference.clamp_lo(), reference.clamp_hi())
+        );
+    }
+
+    #[test]
+    fn builds_with_the_given_settings() {
+        let pid = builder()
+            .kp(1.0)
+            .ki(0.5)
+            .kd(0.2)
+            .smooth(0.3)
+            .derivative_mode(DerivativeMode::OnMeasurement)
+            .direction(ControllerDirection::Reverse)
+            .build()
+            .unwrap();
+        assert_eq!((pid.kp(), pid.ki(), pid.kd()), (1.0, 0.5, 0.2));
+        assert_eq!(pid.dt(), 0.1);
+        assert_eq!((pid.clamp_lo(), pid.clamp_hi()), (-1.0, 1.0));
+        assert_eq!(pid.smooth(), 0.3);
+        assert_eq!(pid.derivative_mode(), DerivativeMode::OnMeasurement);
+        assert_eq!(pid.direction(), ControllerDirection::Reverse);
+    }
+
+    #[test]
+    fn matches_a_manually_configured_controller() {
+        let mut built = PidController::builder()
+            .dt(0.01)
+            .kp(1.2)
+            .ki(0.4)
+            .kd(0.05)
+            .clamp(-10.0, 10.0)
+            .smooth(0.6)
+            .derivative_mode(DerivativeMode::OnMeasurement)
+            .direction(ControllerDirection::Reverse)
+            .build()
+            .unwrap();
+
+        let mut manual = PidController::new(0.01, (-10.0, 10.0));
+        manual.set_kp(1.2).unwrap();
+        manual.set_ki(0.4).unwrap();
+        manual.set_kd(0.05).unwrap();
+        manual.set_smooth(0.6).unwrap();
+        manual.set_derivative_mode(DerivativeMode::OnMeasurement);
+        manual.set_direction(ControllerDirection::Reverse);
+        for k in 0..100 {
+            let y = (k as f64 * 0.1).sin();
+            assert_eq!(
+                built.step_with_setpoint(0.5, y),
+                manual.step_with_setpoint(0.5, y)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_nan() {
+        assert_eq!(builder().dt(f64::NAN).build().err(), Some(PidError::NaN));
+        assert_eq!(
+            builder().clamp(f64::NAN, 1.0).build().err(),
+            Some(PidError::NaN)
+        );
+        assert_eq!(builder().kp(f64::NAN).build().err(), Some(PidError::NaN));
+        assert_eq!(builder().ki(f64::NAN).build().err(), Some(PidError::NaN));
+        assert_eq!(builder().kd(f64::NAN).build().err(), Some(PidError::NaN));
+        assert_eq!(
+            builder().smooth(f64::NAN).build().err(),
+            Some(PidError::NaN)
+        );
+    }
+
+    #[test]
+    fn rejects_non_positive_dt() {
+        assert_eq!(
+            builder().dt(0.0).build().err(),
+            Some(PidError::NonPositiveDt)
+        );
+        assert_eq!(
+            builder().dt(-0.1).build().err(),
+            Some(PidError::NonPositiveDt)
+        );
+    }
+
+    #[test]
+    fn rejects_inverted_clamp() {
+        assert_eq!(
+            builder().clamp(1.0, -1.0).build().err(),
+            Some(PidError::InvertedClamp)
+        );
+        assert_eq!(
+            builder().clamp(1.0, 1.0).build().err(),
+            Some(PidError::InvertedClamp)
+        );
+    }
+
+    #[test]
+    fn rejects_negative_gains() {
+        assert_eq!(
+            builder().kp(-1.0).build().err(),
+            Some(PidError::NegativeGain)
+        );
+        assert_eq!(
+            builder().ki(-1.0).build().err(),
+            Some(PidError::NegativeGain)
+        );
+        assert_eq!(
+            builder().kd(-1.0).build().err(),
+            Some(PidError::NegativeGain)
+        );
+    }
+
+    #[test]
+    fn rejects_smooth_out_of_range() {
+        assert_eq!(
+            builder().smooth(1.5).build().err(),
+            Some(PidError::SmoothOutOfRange)
+        );
+        assert_eq!(
+            builder().smooth(-0.1).build().err(),
+            Some(PidError::SmoothOutOfRange)
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{ControllerDirection, PidController, Saturation};
+
+use num_traits::Float;
+
+/// two nested loops: the outer controller's output is the inner
+/// controller's setpoint.
+///
+/// step() runs at the inner rate. The outer loop steps once every
+/// outer.dt() / inner.dt() inner steps, rounded, at least every step.
+/// While the inner loop saturates, the outer loop treats its own output as
+/// saturated the same way, so the outer integrator does not wind up against
+/// an inner loop that cannot follow.
+///
+/// The outer loop's clamp range is the range of inner setpoints, so set it
+/// to what the inner loop may be asked to track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CascadeController<T = f64> {
+    outer: PidController<T>,
+    inner: PidController<T>,
+
+    /// inner steps per outer step
+    ratio: usize,
+
+    /// inner steps since the last outer step
+    count: usize,
+
+    /// last output of the outer loop
+    inner_setpoint: T,
+
+    /// saturation of the outer loop's own last step
+    outer_saturation: Saturation,
+}
+
+impl<T: Float> CascadeController<T> {
+    pub fn new(outer: PidController<T>, inner: PidController<T>) -> Self {
+        let ratio = (outer.dt() / inner.dt()).round().to_usize().unwrap_or(1);
+        Self::with_ratio(outer, inner, ratio)
+    }
+
+    /// new() with the number of inner steps per outer step given instead of
+    /// derived from the time steps. 0 counts as 1.
+    pub fn with_ratio(
+        outer: PidController<T>,
+        inner: PidController<T>,
+        inner_steps_per_outer: usize,
+    ) -> Self {
+        let ratio = inner_steps_per_outer.max(1);
+        Self {
+            outer,
+            inner,
+            ratio,
+            count: 0,
+            inner_setpoint: T::zero(),
+            outer_saturation: Saturation::None,
+        }
+    }
+
+    /// setpoint: outer setpoint
+    /// outer_measurement: process value of the outer loop
+    /// inner_measurement: process value of the inner loop
+    /// returns the inner controller's output
+    pub fn step(&mut self, setpoint: T, outer_measurement: T, inner_measurement: T) -> T {
+        if self.count == 0 {
+            self.inner_setpoint = self.outer.step_with_setpoint(setpoint, outer_measurement);
+            self.outer_saturation = self.outer.saturation();
+        }
+        self.step_inner(inner_measurement)
+    }
+
+    /// step() with the outer loop fed a precomputed error, as in
+    /// PidController::step()
+    pub fn step_error(&mut self, outer_error: T, inner_measurement: T) -> T {
+        if self.count == 0 {
+            self.inner_setpoint = self.outer.step(outer_error);
+            self.outer_saturation = self.outer.saturation();
+        }
+        self.step_inner(inner_measurement)
+    }
+
+    /// runs the inner loop and hands its saturation to the outer loop
+    fn step_inner(&mut self, inner_measurement: T) -> T {
+        self.count = (self.count + 1) % self.ratio;
+
+        let u = self
+            .inner
+            .step_with_setpoint(self.inner_setpoint, inner_measurement);
+
+        let inner_saturation = match (self.inner.saturation(), self.inner.direction()) {
+            (s, ControllerDirection::Direct) => s,
+            (Saturation::Upper, ControllerDirection::Reverse) => Saturation::Lower,
+            (Saturation::Lower, ControllerDirection::Reverse) => Saturation::Upper,
+            (Saturation::None, ControllerDirection::Reverse) => Saturation::None,
+        };
+        let mut state = self.outer.state();
+        state.saturation = match inner_saturation {
+            Saturation::None => self.outer_saturation,
+            s => s,
+        };
+        self.outer.restore(&state);
+
+        u
+    }
+
+    /// clears both loops
+    pub fn reset(&mut self) {
+        self.outer.reset();
+        self.inner.reset();
+        self.count = 0;
+        self.inner_setpoint = T::zero();
+        self.outer_saturation = Saturation::None;
+    }
+
+    pub fn outer(&self) -> &PidController<T> {
+        &self.outer
+    }
+
+    pub fn inner(&self) -> &PidController<T> {
+        &self.inner
+    }
+
+    pub fn outer_mut(&mut self) -> &mut PidController<T> {
+        &mut self.outer
+    }
+
+    pub fn inner_mut(&mut self) -> &mut PidController<T> {
+        &mut self.inner
+    }
+
+    pub fn ratio(&self) -> usize {
+        self.ratio
+    }
+
+    /// setpoint the outer loop currently gives the inner loop
+    pub fn inner_setpoint(&self) -> T {
+        self.inner_setpoint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// position loop around a velocity loop: v' = (u - v) / 0.1, x' = v
+    fn run(cascade: &mut CascadeController<f64>, steps: usize) -> (f64, f64) {
+        let dt = cascade.inner().dt();
+        let (mut x, mut v) = (0.0, 0.0);
+        for _ in 0..steps {
+            let u = cascade.step(1.0, x, v);
+            v += dt * (u - v) / 0.1;
+            x += dt * v;
+        }
+        (x, v)
+    }
+
+    fn cascade(outer_dt: f64) -> CascadeController<f64> {
+        let mut outer = PidController::new(outer_dt, (-2.0, 2.0));
+        outer.set_kp(2.0).unwrap();
+        let mut inner = PidController::new(0.001, (-5.0, 5.0));
+        inner.set_gains(5.0, 10.0, 0.0).unwrap();
+        CascadeController::new(outer, inner)
+    }
+
+    #[test]
+    fn settles_a_two_stage_plant() {
+        let mut cascade = cascade(0.01);
+        assert_eq!(cascade.ratio(), 10);
+        let (x, v) = run(&mut cascade, 20_000);
+        assert!((x - 1.0).abs() < 1e-3, "{}", x);
+        assert!(v.abs() < 1e-3, "{}", v);
+        assert!(cascade.inner_setpoint().abs() < 1e-2);
+    }
+
+    #[test]
+    fn outer_loop_runs_at_its_own_rate() {
+        let mut cascade = cascade(0.01);
+        cascade.step(0.5, 0.0, 0.0);
+        assert_eq!(cascade.inner_setpoint(), 1.0);
+        for _ in 1..10 {
+            cascade.step(0.25, 0.0, 0.0);
+            assert_eq!(cascade.inner_setpoint(), 1.0);
+        }
+        cascade.step(0.25, 0.0, 0.0);
+        assert_eq!(cascade.inner_setpoint(), 0.5);
+    }
+
+    /// integrated absolute position error after a load disturbance on the
+    /// velocity stage, with the controller mapping (x, v) to u
+    fn disturbance_iae(mut control: impl FnMut(f64, f64) -> f64) -> f64 {
+        let dt = 0.001;
+        let (mut x, mut v) = (0.0, 0.0);
+        let mut iae = 0.0;
+        for k in 0..10_000 {
+            let disturbance = if k >= 2_000 { -1.0 } else { 0.0 };
+            let u = control(x, v);
+            v += dt * (u + disturbance - v) / 0.1;
+            x += dt * v;
+            if k >= 2_000 {
+                iae += dt * x.abs();
+            }
+        }
+        iae
+    }
+
+    #[test]
+    fn rejects_inner_disturbances_better_than_a_single_loop() {
+        let mut cascade = cascade(0.001);
+        let cascaded = disturbance_iae(|x, v| cascade.step(0.0, x, v));
+
+        // a single PID with the cascade's proportional gain from position to
+        // output, 2 * 5
+        let mut single = PidController::new(0.001, (-5.0, 5.0));
+        single.set_gains(10.0, 5.0, 2.0).unwrap();
+        single.set_derivative_mode(crate::DerivativeMode::OnMeasurement);
+        let single = disturbance_iae(|x, _| single.step_with_setpoint(0.0, x));
+        assert!(cascaded < single / 2.0, "{} vs {}", cascaded, single);
+    }
+
+    #[test]
+    fn inner_saturation_stops_the_outer_integrator() {
+        let mut outer = PidController::new(0.001, (-100.0, 100.0));
+        outer.set_gains(1.0, 1.0, 0.0).unwrap();
+        let mut inner = PidController::new(0.001, (-1.0, 1.0));
+        inner.set_kp(10.0).unwrap();
+        let mut cascade = CascadeController::new(outer, inner);
+        cascade.step(10.0, 0.0, 0.0);
+        let i = cascade.outer().i();
+        for _ in 0..100 {
+            cascade.step(10.0, 0.0, 0.0);
+        }
+        assert_eq!(cascade.outer().i(), i);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{check_clamp, check_gain, PidController, PidError};
+
+use num_traits::Float;
+
+/// common interface of the controllers in this crate, object safe so that
+/// different kinds can share a `dyn Controller`.
+///
+/// ```
+/// use pid::{Controller, PController, PiController, PidController};
+///
+/// let mut pid = PidController::<f64>::new(0.1, (-1.0, 1.0));
+/// pid.set_gains(0.5, 0.2, 0.1).unwrap();
+/// let mut axes: Vec<Box<dyn Controller>> = vec![
+///     Box::new(PController::new(2.0, (-1.0, 1.0)).unwrap()),
+///     Box::new(PiController::new(0.1, (-1.0, 1.0), 0.5, 0.2).unwrap()),
+///     Box::new(pid),
+/// ];
+///
+/// let outputs: Vec<f64> = axes.iter_mut().map(|axis| axis.step(0.25)).collect();
+/// assert_eq!(outputs[0], 0.5);
+/// for axis in &mut axes {
+///     assert_eq!(axis.output_limits(), (-1.0, 1.0));
+///     axis.reset();
+/// }
+/// ```
+pub trait Controller<T = f64> {
+    /// e: error value
+    /// returns the control output within output_limits()
+    fn step(&mut self, e: T) -> T;
+
+    /// clears the runtime state, keeping the configuration
+    fn reset(&mut self);
+
+    /// (lo, hi) bounds of the output
+    fn output_limits(&self) -> (T, T);
+}
+
+impl<T: Float> Controller<T> for PidController<T> {
+    fn step(&mut self, e: T) -> T {
+        PidController::step(self, e)
+    }
+
+    fn reset(&mut self) {
+        PidController::reset(self)
+    }
+
+    fn output_limits(&self) -> (T, T) {
+        (self.clamp_lo(), self.clamp_hi())
+    }
+}
+
+/// proportional-only controller, u = kp * e clamped. stateless, so it
+/// needs no time step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PController<T = f64> {
+    kp: T,
+    clamp_lo: T,
+    clamp_hi: T,
+}
+
+impl<T: Float> PController<T> {
+    pub fn new(kp: T, clamp: (T, T)) -> Result<Self, PidError> {
+        let kp = check_gain(kp)?;
+        let (clamp_lo, clamp_hi) = check_clamp(clamp.0, clamp.1)?;
+        Ok(Self {
+            kp,
+            clamp_lo,
+            clamp_hi,
+        })
+    }
+
+    pub fn set_kp(&mut self, kp: T) -> Result<(), PidError> {
+        self.kp = check_gain(kp)?;
+        Ok(())
+    }
+
+    pub fn kp(&self) -> T {
+        self.kp
+    }
+}
+
+impl<T: Float> Controller<T> for PController<T> {
+    fn step(&mut self, e: T) -> T {
+        (self.kp * e).max(self.clamp_lo).min(self.clamp_hi)
+    }
+
+    fn reset(&mut self) {}
+
+    fn output_limits(&self) -> (T, T) {
+        (self.clamp_lo, self.clamp_hi)
+    }
+}
+
+/// PidController without a derivative term. kd stays zero, everything else
+/// is available read-only through pid().
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PiController<T = f64> {
+    pid: PidController<T>,
+}
+
+impl<T: Float> PiController<T> {
+    pub fn new(dt: T, clamp: (T, T), kp: T, ki: T) -> Result<Self, PidError> {
+        let mut pid = PidController::try_new(dt, clamp)?;
+        pid.set_kp(kp)?;
+        pid.set_ki(ki)?;
+        Ok(Self { pid })
+    }
+
+    pub fn set_kp(&mut self, kp: T) -> Result<(), PidError> {
+        self.pid.set_kp(kp)
+    }
+
+    pub fn set_ki(&mut self, ki: T) -> Result<(), PidError> {
+        self.pid.set_ki(ki)
+    }
+
+    /// the underlying controller
+    pub fn pid(&self) -> &PidController<T> {
+        &self.pid
+    }
+}
+
+impl<T: Float> Controller<T> for PiController<T> {
+    fn step(&mut self, e: T) -> T {
+        self.pid.step(e)
+    }
+
+    fn reset(&mut self) {
+        self.pid.reset()
+    }
+
+    fn output_limits(&self) -> (T, T) {
+        self.pid.output_limits()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    use std::boxed::Box;
+    use std::vec::Vec;
+
+    #[test]
+    fn different_kinds_step_through_one_interface() {
+        let mut pid = PidController::new(0.1, (-1.0, 1.0));
+        pid.set_gains(1.0, 1.0, 0.1).unwrap();
+        let mut reference = pid;
+        let mut axes: Vec<Box<dyn Controller>> = Vec::new();
+        axes.push(Box::new(PController::new(2.0, (-1.0, 1.0)).unwrap()));
+        axes.push(Box::new(
+            PiController::new(0.1, (-1.0, 1.0), 1.0, 1.0).unwrap(),
+        ));
+        axes.push(Box::new(pid));
+
+        for _ in 0..3 {
+            let outputs: Vec<f64> = axes.iter_mut().map(|axis| axis.step(0.25)).collect();
+            assert_eq!(outputs[0], 0.5);
+            assert!(outputs[1] > 0.25);
+            assert_eq!(outputs[2], reference.step(0.25));
+        }
+        assert_eq!(axes[0].step(5.0), 1.0);
+
+        for axis in &mut axes {
+            axis.reset();
+        }
+        reference.reset();
+        assert_eq!(axes[1].step(0.25), 0.25 + 0.025);
+        assert_eq!(axes[2].step(0.25), reference.step(0.25));
+    }
+}
@@ -0,0 +1,82 @@
+use core::fmt;
+
+/// reasons a controller configuration or input is rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PidError {
+    /// a parameter is NaN
+    NaN,
+
+    /// dt is zero or negative
+    NonPositiveDt,
+
+    /// a time constant is zero or negative
+    NonPositiveTimeConstant,
+
+    /// clamp_lo is not below clamp_hi
+    InvertedClamp,
+
+    /// a gain is negative
+    NegativeGain,
+
+    /// a gain is infinite
+    InfiniteGain,
+
+    /// smooth is outside [0,1]
+    SmoothOutOfRange,
+
+    /// output rate limit is zero or negative
+    NonPositiveRateLimit,
+
+    /// a setpoint weight is outside [0,1]
+    WeightOutOfRange,
+
+    /// a threshold or band width is negative
+    NegativeThreshold,
+
+    /// integrator leak rate is negative or infinite
+    LeakOutOfRange,
+
+    /// an input to a step is NaN or infinite
+    NonFinite,
+
+    /// derivative window is zero or above MAX_DERIVATIVE_WINDOW
+    WindowOutOfRange,
+
+    /// a channel index is not below the number of channels
+    ChannelOutOfRange,
+
+    /// a slice does not have one entry per channel
+    LengthMismatch,
+
+    /// derivative filter coefficient is zero or negative
+    NonPositiveFilterCoefficient,
+}
+
+impl fmt::Display for PidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            PidError::NaN => "parameter is NaN",
+            PidError::NonPositiveDt => "time step must be positive",
+            PidError::NonPositiveTimeConstant => "time constant must be positive",
+            PidError::InvertedClamp => "lower clamp must be below upper clamp",
+            PidError::NegativeGain => "gains must not be negative",
+            PidError::InfiniteGain => "gains must be finite",
+            PidError::SmoothOutOfRange => "smoothing coefficient must be within [0,1]",
+            PidError::NonPositiveRateLimit => "output rate limit must be positive",
+            PidError::WeightOutOfRange => "setpoint weights must be within [0,1]",
+            PidError::NegativeThreshold => "thresholds must not be negative",
+            PidError::LeakOutOfRange => "integrator leak must be finite and not negative",
+            PidError::NonFinite => "input is not finite",
+            PidError::WindowOutOfRange => "derivative window is out of range",
+            PidError::ChannelOutOfRange => "channel index is out of range",
+            PidError::LengthMismatch => "slice length does not match the channel count",
+            PidError::NonPositiveFilterCoefficient => {
+                "derivative filter coefficient must be positive"
+            }
+        };
+        f.write_str(msg)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PidError {}
@@ -1,143 +1,1711 @@
-#[derive(Debug)]
-pub struct PidController {
+//! PID controller for `std` and `#![no_std]` targets.
+//!
+//! Give the controller a setpoint and feed it measurements with `update()`.
+//! `step()` takes a precomputed error instead and shares the same state.
+//!
+//! Without `std`, enable the `libm` feature for the float math:
+//!
+//! ```toml
+//! pid = { version = "0.1", default-features = false, features = ["libm"] }
+//! ```
+//!
+//! `GainSchedule`, `GainScheduledController`, `PidBank` and the `sim` module
+//! need an allocator. They are available with `std`, or with the `alloc`
+//! feature on `no_std` targets.
+//!
+//! ```
+//! #![no_std]
+//!
+//! use pid::PidController;
+//!
+//! fn control(pid: &mut PidController<f32>, measurement: f32) -> f32 {
+//!     pid.update(measurement)
+//! }
+//! # fn main() {}
+//! ```
+
+#![no_std]
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("either the `std` or the `libm` feature must be enabled");
+
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod autotune;
+#[cfg(feature = "alloc")]
+mod bank;
+mod builder;
+mod cascade;
+mod controller;
+mod error;
+#[cfg(feature = "std")]
+mod params;
+#[cfg(feature = "alloc")]
+mod schedule;
+#[cfg(feature = "serde")]
+mod serde_impl;
+#[cfg(feature = "alloc")]
+pub mod sim;
+mod tuning;
+
+#[cfg(all(test, feature = "std"))]
+mod tests;
+
+pub use autotune::{AutoTuneState, RelayTuner, TuningResult, TuningStatus};
+#[cfg(feature = "alloc")]
+pub use bank::PidBank;
+pub use builder::PidControllerBuilder;
+pub use cascade::CascadeController;
+pub use controller::{Controller, PController, PiController};
+pub use error::PidError;
+#[cfg(feature = "std")]
+pub use params::PidParams;
+#[cfg(feature = "alloc")]
+pub use schedule::{GainSchedule, GainScheduledController};
+pub use tuning::TuningRule;
+
+use core::time::Duration;
+use num_traits::Float;
+
+/// parallel-form gains, u = kp * e + ki * integral(e) + kd * de/dt
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PidGains<T = f64> {
+    pub kp: T,
+    pub ki: T,
+    pub kd: T,
+}
+
+/// per-term breakdown of a single step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PidOutput<T = f64> {
+    /// proportional contribution, kp * e
+    pub p: T,
+
+    /// integral contribution, ki * i
+    pub i: T,
+
+    /// derivative contribution, kd * d
+    pub d: T,
+
+    /// output after saturation, as returned by step()
+    pub total: T,
+
+    /// whether the unsaturated output was outside the clamp range
+    pub saturated: bool,
+}
+
+/// dynamic state of a PidController, without its configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PidState<T = f64> {
+    /// proportional term, kp * e[n]
+    pub p: T,
+
+    /// integral term, the accumulated error. contributes ki * i to the output.
+    pub i: T,
+
+    /// differential term, the smoothed derivative of the error.
+    /// contributes kd * d to the output.
+    pub d: T,
+
+    /// clamp bound the unsaturated output reached in the last step, or the
+    /// direction the rate limit held it back in
+    pub saturation: Saturation,
+
+    /// number of steps whose output was saturated, see saturation_count()
+    pub saturation_count: u64,
+
+    /// unsaturated output of the last step
+    pub u_raw: T,
+
+    /// previous error
+    pub e_prev: T,
+
+    /// error before e_prev
+    pub e_prev2: T,
+
+    /// output of the input filter
+    pub e_filtered: T,
+
+    /// previous measurement, only tracked by step_with_setpoint()
+    pub y_prev: T,
+
+    /// previous setpoint, only tracked by step_with_setpoint()
+    pub r_prev: T,
+
+    /// whether y_prev and r_prev hold a sample, false until the first
+    /// step_with_setpoint() after new() or reset()
+    pub sampled: bool,
+
+    /// output returned by the last step
+    pub u_prev: T,
+
+    /// recent changes of the differentiated signal, see
+    /// set_derivative_window()
+    pub de_history: [T; MAX_DERIVATIVE_WINDOW],
+
+    /// slot of de_history the next change is written to
+    pub de_pos: usize,
+
+    /// setpoint update() ramps towards setpoint(), None until the first
+    /// update()
+    pub r_ramped: Option<T>,
+}
+
+impl<T: Float> Default for PidState<T> {
+    fn default() -> Self {
+        Self {
+            p: T::zero(),
+            i: T::zero(),
+            d: T::zero(),
+            saturation: Saturation::None,
+            saturation_count: 0,
+            u_raw: T::zero(),
+            e_prev: T::zero(),
+            e_prev2: T::zero(),
+            e_filtered: T::zero(),
+            y_prev: T::zero(),
+            r_prev: T::zero(),
+            sampled: false,
+            u_prev: T::zero(),
+            de_history: [T::zero(); MAX_DERIVATIVE_WINDOW],
+            de_pos: 0,
+            r_ramped: None,
+        }
+    }
+}
+
+/// longest window accepted by set_derivative_window()
+pub const MAX_DERIVATIVE_WINDOW: usize = 16;
+
+/// signal the derivative term differentiates in step_with_setpoint()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DerivativeMode {
+    /// d acts on the error, setpoint steps cause a derivative kick
+    OnError,
+
+    /// d acts on the negated measurement, setpoint steps cause no kick.
+    /// the first measurement after new() or reset() only seeds the
+    /// derivative.
+    OnMeasurement,
+}
+
+/// how the integrator is kept from winding up while the output saturates
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AntiWindup<T = f64> {
+    /// freeze the integrator while the output is outside the clamp range
+    Clamp,
+
+    /// freeze the integrator only while the error drives the output further
+    /// into the bound it saturates at. the default.
+    ConditionalIntegration,
+
+    /// always integrate, and feed the saturation excess back into the
+    /// integrator: i += dt * kaw * (u_sat - u). recovers faster than Clamp.
+    /// the feedback enters the error accumulator, so the tracking gain seen
+    /// by the output is ki * kaw. kaw = 1 / ki gives a textbook tracking
+    /// gain of one.
+    BackCalculation { kaw: T },
+
+    /// always integrate, whatever the saturation. the integrator winds up
+    /// during saturation and has to unwind before the output leaves the
+    /// bound, which causes overshoot. useful when the clamp is never reached
+    /// or the windup is handled outside the controller.
+    None,
+}
+
+/// discretization of the integral and the filtered derivative
+///
+/// ```
+/// use pid::{AntiWindup, Integration, PidController};
+///
+/// // integrate sin(t) over [0, 2] at dt = 0.1
+/// let integral = |method| {
+///     let mut pid = PidController::<f64>::new(0.1, (-10.0, 10.0));
+///     pid.set_gains(0.0, 1.0, 0.0).unwrap();
+///     pid.set_anti_windup(AntiWindup::None).unwrap();
+///     pid.set_integration_method(method);
+///     (0..=20).map(|k| pid.step((k as f64 * 0.1).sin())).last().unwrap()
+/// };
+/// let exact = 1.0 - 2.0f64.cos();
+/// let euler = (integral(Integration::ForwardEuler) - exact).abs();
+/// let trapezoidal = (integral(Integration::Trapezoidal) - exact).abs();
+/// assert!(trapezoidal < euler / 10.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Integration {
+    /// i += dt * e[n], and the smoothed backward difference for the
+    /// derivative. the default.
+    ForwardEuler,
+
+    /// i += dt * (e[n] + e[n-1]) / 2, and the Tustin (bilinear) form of the
+    /// derivative filter. more accurate at coarse time steps.
+    Trapezoidal,
+}
+
+/// what a step does with a NaN or infinite input. under every policy the
+/// input is kept out of the state, so the controller continues normally
+/// once the input is finite again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NanPolicy {
+    /// return NaN for that step. the default.
+    Propagate,
+
+    /// return the output of the last step
+    HoldLastOutput,
+
+    /// clear the state as reset() does and return the output of the last
+    /// step
+    ResetAndHold,
+}
+
+/// whether the controller or the operator drives the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mode {
+    /// step() computes the output
+    Auto,
+
+    /// step() returns the held output, see set_manual_output(), and tracks
+    /// it with the integral
+    Manual,
+}
+
+/// which clamp bound, if any, the unsaturated output reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Saturation {
+    None,
+    Upper,
+    Lower,
+}
+
+/// how the output responds to the error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ControllerDirection {
+    /// positive error raises the output
+    Direct,
+
+    /// positive error lowers the output, e.g. a cooling loop
+    Reverse,
+}
+
+/// T is the float type used for all parameters and state, f64 unless given.
+///
+/// With the serde feature, the tuning and the runtime state are serialized,
+/// so a checkpoint resumes exactly where it left off. Infinite limits and
+/// disabled rate limits are written as none, so formats without infinity
+/// such as JSON can hold them. Deserialization rejects values the setters
+/// would reject.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        remote = "Self",
+        bound(
+            serialize = "T: Float + serde::Serialize",
+            deserialize = "T: Float + serde::Deserialize<'de>"
+        )
+    )
+)]
+pub struct PidController<T = f64> {
     /// time step
-    dt: f64,
+    dt: T,
 
     /// proportional gain
-    kp: f64,
+    kp: T,
 
     /// integral gain
-    ki: f64,
+    ki: T,
 
     /// derivative gain
-    kd: f64,
+    kd: T,
 
     /// clamp values for anti-windup
-    clamp_lo: f64,
-    clamp_hi: f64,
+    #[cfg_attr(feature = "serde", serde(with = "serde_impl::unbounded_below"))]
+    clamp_lo: T,
+    #[cfg_attr(feature = "serde", serde(with = "serde_impl::unbounded"))]
+    clamp_hi: T,
 
     /// coefficient of simple exponential smoothing for differential term.
     /// valid range is [0,1].
     /// d[n] = smooth * (e[n] - e[n-1]) / dt + (1 - smooth) * d[n-1]
     ///    where d[n] is differential term and e[n] is error value.
     /// If smooth = 1, smoothing function is off.
-    smooth: f64,
+    smooth: T,
 
-    /// proportional term
-    p: f64,
+    /// filter coefficient n of kd * s / (1 + (kd / n) * s), replaces smooth
+    derivative_filter: Option<T>,
 
-    /// integral term
-    i: f64,
+    /// number of steps the derivative is taken over, 1 for e[n] - e[n-1]
+    derivative_window: usize,
 
-    /// differential term
-    d: f64,
+    /// signal differentiated by step_with_setpoint()
+    derivative_mode: DerivativeMode,
 
-    /// clamped or not
-    unclamped: bool,
+    /// skip the derivative of steps where the setpoint changed
+    prevent_derivative_kick: bool,
 
-    /// previous error
-    e_prev: f64,
+    /// setpoint weights of the proportional and derivative terms in
+    /// step_with_setpoint(). valid range is [0,1].
+    b: T,
+    c: T,
+
+    /// Reverse negates the error before it enters the terms
+    direction: ControllerDirection,
+
+    /// anti-windup scheme
+    anti_windup: AntiWindup<T>,
+
+    /// maximum change of the output per unit time, infinite when disabled
+    #[cfg_attr(feature = "serde", serde(with = "serde_impl::unbounded"))]
+    max_rate: T,
+
+    /// constant offset added to the output before saturation
+    bias: T,
+
+    /// integration is skipped while |e| exceeds this, infinite when disabled
+    #[cfg_attr(feature = "serde", serde(with = "serde_impl::unbounded"))]
+    integral_threshold: T,
+
+    /// errors with |e| below this are treated as zero, 0 when disabled
+    deadband: T,
+
+    /// automatic or manual operation
+    mode: Mode,
+
+    /// handling of non-finite step inputs
+    nan_policy: NanPolicy,
+
+    /// discretization of the I and D terms
+    integration: Integration,
+
+    /// setpoint used by update()
+    setpoint: T,
+
+    /// how fast update() moves the effective setpoint up and down, per unit
+    /// time. infinite when disabled.
+    #[cfg_attr(feature = "serde", serde(with = "serde_impl::unbounded"))]
+    setpoint_ramp_up: T,
+    #[cfg_attr(feature = "serde", serde(with = "serde_impl::unbounded"))]
+    setpoint_ramp_down: T,
+
+    /// bounds of the integral contribution ki * i, unbounded if None
+    #[cfg_attr(feature = "serde", serde(with = "serde_impl::limits"))]
+    integral_limits: Option<(T, T)>,
+
+    /// coefficient of the first-order low-pass on the incoming error.
+    /// valid range is [0,1], 1 means unfiltered.
+    /// e_f[n] = input_filter * e[n] + (1 - input_filter) * e_f[n-1]
+    input_filter: T,
+
+    /// integrator leak rate, i decays by exp(-leak * dt) every step
+    leak: T,
+
+    /// runtime state, see state()
+    state: PidState<T>,
 }
 
-impl PidController {
-    pub fn new(dt: f64, clamp: (f64, f64)) -> Self {
-        Self {
+impl<T: Float> PidController<T> {
+    /// # Panics
+    ///
+    /// if dt is not positive or clamp.0 is not below clamp.1, see try_new().
+    pub fn new(dt: T, clamp: (T, T)) -> Self {
+        match Self::try_new(dt, clamp) {
+            Ok(pid) => pid,
+            Err(err) => panic!("invalid PidController: {}", err),
+        }
+    }
+
+    /// new() returning an error instead of panicking on a non-positive dt or
+    /// an empty or inverted clamp range
+    pub fn try_new(dt: T, clamp: (T, T)) -> Result<Self, PidError> {
+        let dt = check_dt(dt)?;
+        let clamp = check_clamp(clamp.0, clamp.1)?;
+        Ok(Self {
             dt,
-            kp: 0.0,
-            ki: 0.0,
-            kd: 0.0,
+            kp: T::zero(),
+            ki: T::zero(),
+            kd: T::zero(),
             clamp_lo: clamp.0,
             clamp_hi: clamp.1,
-            smooth: 1.0,
-            p: 0.0,
-            i: 0.0,
-            d: 0.0,
-            unclamped: true,
-            e_prev: 0.0,
+            smooth: T::one(),
+            derivative_filter: None,
+            derivative_window: 1,
+            derivative_mode: DerivativeMode::OnError,
+            prevent_derivative_kick: false,
+            b: T::one(),
+            c: T::one(),
+            direction: ControllerDirection::Direct,
+            anti_windup: AntiWindup::ConditionalIntegration,
+            max_rate: T::infinity(),
+            bias: T::zero(),
+            integral_threshold: T::infinity(),
+            deadband: T::zero(),
+            mode: Mode::Auto,
+            nan_policy: NanPolicy::Propagate,
+            integration: Integration::ForwardEuler,
+            setpoint: T::zero(),
+            setpoint_ramp_up: T::infinity(),
+            setpoint_ramp_down: T::infinity(),
+            integral_limits: None,
+            input_filter: T::one(),
+            leak: T::zero(),
+            state: PidState::default(),
+        })
+    }
+
+    /// try_new() with dt taken from a loop period, e.g. the interval of a
+    /// timer. a zero period is rejected like a zero dt.
+    ///
+    /// ```
+    /// use core::time::Duration;
+    /// use pid::PidController;
+    ///
+    /// let pid = PidController::<f64>::from_duration(Duration::from_millis(10), (-1.0, 1.0)).unwrap();
+    /// assert_eq!(pid.dt(), 0.01);
+    /// assert!(PidController::<f64>::from_duration(Duration::ZERO, (-1.0, 1.0)).is_err());
+    /// ```
+    pub fn from_duration(period: Duration, clamp: (T, T)) -> Result<Self, PidError> {
+        let dt = T::from(period.as_secs_f64()).unwrap_or_else(T::nan);
+        Self::try_new(dt, clamp)
+    }
+
+    /// new() with the output clamped to [-half_range, half_range].
+    ///
+    /// # Panics
+    ///
+    /// if half_range is not positive.
+    pub fn new_symmetric(dt: T, half_range: T) -> Self {
+        assert!(half_range > T::zero(), "half_range must be positive");
+        Self::new(dt, (-half_range, half_range))
+    }
+
+    pub fn builder() -> PidControllerBuilder<T> {
+        PidControllerBuilder::default()
+    }
+
+    /// e: error value
+    /// returns the control output saturated to [clamp_lo, clamp_hi].
+    /// A NaN or infinite e is handled by the NaN policy, see
+    /// set_nan_policy(), and never reaches the state. try_step() reports it
+    /// as an error instead.
+    pub fn step(&mut self, e: T) -> T {
+        self.step_detailed(e).total
+    }
+
+    /// same as step(), but rejects a NaN or infinite e with
+    /// PidError::NonFinite and leaves the state untouched, so the controller
+    /// keeps working once valid input returns.
+    pub fn try_step(&mut self, e: T) -> Result<T, PidError> {
+        if !e.is_finite() {
+            return Err(PidError::NonFinite);
+        }
+        Ok(self.step(e))
+    }
+
+    /// same as step(), but also reports the contribution of each term.
+    pub fn step_detailed(&mut self, e: T) -> PidOutput<T> {
+        self.step_error(e, self.dt, T::zero())
+    }
+
+    /// same as step(), but integrates and differentiates over dt instead of
+    /// the configured time step, for loops without a fixed rate.
+    /// The smoothing of the derivative keeps the time constant it has at the
+    /// configured dt. A zero or negative dt neither integrates nor updates
+    /// the derivative, only the proportional term follows e. A NaN or
+    /// infinite dt is handled like a non-finite e.
+    pub fn step_with_dt(&mut self, e: T, dt: T) -> T {
+        self.step_error(e, dt, T::zero()).total
+    }
+
+    /// step_with_dt() with the time since the last step as a Duration
+    pub fn step_elapsed(&mut self, e: T, elapsed: Duration) -> T {
+        let dt = T::from(elapsed.as_secs_f64()).unwrap_or_else(T::nan);
+        self.step_with_dt(e, dt)
+    }
+
+    /// same as step(), with a feedforward term ff added to the output before
+    /// saturation. ff bypasses the integrator, but the anti-windup decision is
+    /// made on the combined output.
+    pub fn step_ff(&mut self, e: T, ff: T) -> T {
+        self.step_error(e, self.dt, ff).total
+    }
+
+    /// velocity form: returns the change of the output instead of the output,
+    /// du = kp * (e[n] - e[n-1]) + ki * dt * e[n] + kd * (e[n] - 2 e[n-1] + e[n-2]) / dt
+    /// (with Integration::Trapezoidal, ki * dt * (e[n] + e[n-1]) / 2)
+    /// the caller accumulates du, e.g. in an integrating actuator. |du| is
+    /// limited to output_rate_limit() * dt. Clamping, anti-windup and
+    /// smoothing do not apply. none is needed: the integral lives in the
+    /// accumulated output, so once the caller or the actuator stops it at a
+    /// limit there is no separate integrator left to wind up.
+    /// summing du from a reset controller gives the positional output of
+    /// step() for the same errors, as long as neither saturates and there
+    /// is no smoothing, deadband or bias.
+    /// A non-finite e returns NaN under NanPolicy::Propagate and no change
+    /// otherwise.
+    pub fn step_incremental(&mut self, e: T) -> T {
+        if let Some(held) = self.non_finite(e.is_finite()) {
+            // the velocity form holds its output by not changing it
+            return if self.nan_policy == NanPolicy::Propagate {
+                held.total
+            } else {
+                T::zero()
+            };
+        }
+        let e = self.filter_input(e);
+        let e1 = self.state.e_prev;
+        let e2 = self.state.e_prev2;
+        self.state.e_prev2 = e1;
+        self.state.e_prev = e;
+
+        let integrand = match self.integration {
+            Integration::ForwardEuler => e,
+            Integration::Trapezoidal => (e + e1) / (T::one() + T::one()),
+        };
+        let du = self.kp * (e - e1)
+            + self.ki * self.dt * integrand
+            + self.kd * (e - e1 - e1 + e2) / self.dt;
+        let max_step = self.max_rate * self.dt;
+        self.signed(du).max(-max_step).min(max_step)
+    }
+
+    /// setpoint: desired value
+    /// measurement: current process value
+    /// the error is setpoint - measurement, so a measurement below the setpoint
+    /// raises the output of a Direct controller. With the default
+    /// DerivativeMode::OnError this equals step(setpoint - measurement).
+    /// The derivative term follows derivative_mode, step() always
+    /// differentiates the error.
+    /// This is the two-degree-of-freedom form: P acts on b * setpoint -
+    /// measurement and, in OnError mode, D acts on c * setpoint - measurement.
+    /// I always acts on the full error.
+    pub fn step_with_setpoint(&mut self, setpoint: T, measurement: T) -> T {
+        if let Some(held) = self.non_finite(setpoint.is_finite() && measurement.is_finite()) {
+            return held.total;
+        }
+        let e = self.filter_input(setpoint - measurement);
+        // b * r - y and c * r - y, written relative to e
+        let ep = e - (T::one() - self.b) * setpoint;
+        // the first measurement has nothing to be differentiated against, it
+        // seeds y_prev instead of kicking the derivative. kick prevention
+        // treats the first setpoint as a change for the same reason.
+        let first = !self.state.sampled;
+        if first {
+            self.state.y_prev = measurement;
+            self.state.sampled = true;
+        }
+        let de = match self.derivative_mode {
+            DerivativeMode::OnError
+                if self.prevent_derivative_kick && (first || setpoint != self.state.r_prev) =>
+            {
+                // drop the derivative history, so the term is zero this step
+                self.state.d = T::zero();
+                self.state.de_history = [T::zero(); MAX_DERIVATIVE_WINDOW];
+                T::zero()
+            }
+            DerivativeMode::OnError => {
+                e - self.state.e_prev - (T::one() - self.c) * (setpoint - self.state.r_prev)
+            }
+            DerivativeMode::OnMeasurement => self.state.y_prev - measurement,
+        };
+        self.state.r_prev = setpoint;
+        self.state.y_prev = measurement;
+
+        self.advance(e, ep, de, self.dt, T::zero()).total
+    }
+
+    /// step_with_setpoint() against the stored setpoint, see set_setpoint().
+    /// with a setpoint ramp the effective setpoint moves towards it at the
+    /// ramp rate, starting from the first measurement after new() or
+    /// reset().
+    pub fn update(&mut self, measurement: T) -> T {
+        if let Some(held) = self.non_finite(measurement.is_finite()) {
+            return held.total;
+        }
+        let r = self.state.r_ramped.unwrap_or(measurement);
+        let r = r
+            + (self.setpoint - r)
+                .max(-self.setpoint_ramp_down * self.dt)
+                .min(self.setpoint_ramp_up * self.dt);
+        self.state.r_ramped = Some(r);
+        self.step_with_setpoint(r, measurement)
+    }
+
+    /// checks the invariants the setters maintain
+    #[cfg(feature = "serde")]
+    fn validate(&self) -> Result<(), PidError> {
+        check_dt(self.dt)?;
+        check_clamp(self.clamp_lo, self.clamp_hi)?;
+        check_gain(self.kp)?;
+        check_gain(self.ki)?;
+        check_gain(self.kd)?;
+        check_smooth(self.smooth)?;
+        if let Some(n) = self.derivative_filter {
+            check_filter_coefficient(n)?;
+        }
+        check_window(self.derivative_window)?;
+        check_weight(self.b)?;
+        check_weight(self.c)?;
+        check_anti_windup(self.anti_windup)?;
+        check_rate_limit(self.max_rate)?;
+        if self.bias.is_nan() {
+            return Err(PidError::NaN);
+        }
+        check_threshold(self.integral_threshold)?;
+        check_threshold(self.deadband)?;
+        check_leak(self.leak)?;
+        if self.setpoint.is_nan() {
+            return Err(PidError::NaN);
+        }
+        check_rate_limit(self.setpoint_ramp_up)?;
+        check_rate_limit(self.setpoint_ramp_down)?;
+        if let Some((lo, hi)) = self.integral_limits {
+            check_clamp(lo, hi)?;
+        }
+        check_smooth(self.input_filter)?;
+        Ok(())
+    }
+
+    /// positional step on a raw error
+    fn step_error(&mut self, e: T, dt: T, ff: T) -> PidOutput<T> {
+        if let Some(held) = self.non_finite(e.is_finite() && ff.is_finite() && dt.is_finite()) {
+            return held;
+        }
+        let e = self.filter_input(e);
+        self.advance(e, e, e - self.state.e_prev, dt, ff)
+    }
+
+    /// the output for a step whose inputs are not all finite, None if they
+    /// are. see NanPolicy.
+    fn non_finite(&mut self, finite: bool) -> Option<PidOutput<T>> {
+        if finite {
+            return None;
+        }
+        match self.nan_policy {
+            NanPolicy::Propagate => Some(PidOutput {
+                p: T::nan(),
+                i: T::nan(),
+                d: T::nan(),
+                total: T::nan(),
+                saturated: false,
+            }),
+            NanPolicy::HoldLastOutput => Some(self.held()),
+            NanPolicy::ResetAndHold => {
+                let u = self.state.u_prev;
+                self.reset();
+                self.state.u_raw = u;
+                self.state.u_prev = u;
+                Some(self.held())
+            }
         }
     }
 
+    /// the last output, with the current terms
+    fn held(&self) -> PidOutput<T> {
+        PidOutput {
+            p: self.state.p,
+            i: self.ki * self.state.i,
+            d: self.kd * self.state.d,
+            total: self.state.u_prev,
+            saturated: false,
+        }
+    }
+
+    /// low-pass filters the incoming error, see set_input_filter()
+    fn filter_input(&mut self, e: T) -> T {
+        self.state.e_filtered = if self.input_filter == T::one() {
+            e
+        } else {
+            self.input_filter * e + (T::one() - self.input_filter) * self.state.e_filtered
+        };
+        self.state.e_filtered
+    }
+
     /// e: error value
-    pub fn step(&mut self, e: f64) -> f64 {
-        if self.unclamped {
-            self.i += self.dt * e;
+    /// ep: error seen by the proportional term
+    /// de: change of the differentiated signal since the last step
+    /// dt: time since the last step
+    /// ff: feedforward added to the output
+    fn advance(&mut self, e: T, ep: T, de: T, dt: T, ff: T) -> PidOutput<T> {
+        if self.mode == Mode::Manual {
+            self.track(self.state.u_prev, e, ep, de, dt, ff);
+            return self.held();
+        }
+
+        let e_last = self.state.e_prev;
+        self.state.e_prev2 = self.state.e_prev;
+        self.state.e_prev = e;
+        // outside the deadband, P and I see the error shifted towards zero by
+        // the band width, so the output does not jump at the band edge. the
+        // setpoint weighting of ep is kept as it is. D sees the true change.
+        let e_dead = if e.abs() < self.deadband {
+            T::zero()
+        } else {
+            e - self.deadband * e.signum()
+        };
+        let (e, ep, de) = (
+            self.signed(e_dead),
+            self.signed(e_dead + (ep - e)),
+            self.signed(de),
+        );
+
+        let dt = if dt > T::zero() { dt } else { T::zero() };
+
+        if self.leak > T::zero() {
+            self.state.i = self.state.i * (-self.leak * dt).exp();
+        }
+
+        let integrate = match self.anti_windup {
+            AntiWindup::Clamp => self.state.saturation == Saturation::None,
+            AntiWindup::ConditionalIntegration => match self.state.saturation {
+                Saturation::None => true,
+                Saturation::Upper => e <= T::zero(),
+                Saturation::Lower => e >= T::zero(),
+            },
+            AntiWindup::BackCalculation { .. } | AntiWindup::None => true,
+        };
+        if integrate && e.abs() <= self.integral_threshold {
+            let integrand = match self.integration {
+                Integration::ForwardEuler => e,
+                Integration::Trapezoidal => (e + self.integrand(e_last)) / (T::one() + T::one()),
+            };
+            self.state.i = self.state.i + dt * integrand;
+        }
+        self.limit_integral();
+
+        self.update_derivative(de, dt);
+
+        self.state.p = self.kp * ep;
+
+        let u = self.state.p + self.ki * self.state.i + self.kd * self.state.d + ff + self.bias;
+
+        self.state.u_raw = u;
+
+        // rate limit first and clamp last, so the output stays within the
+        // clamp range even when the last output does not
+        let max_step = self.max_rate * dt;
+        let total = u
+            .max(self.state.u_prev - max_step)
+            .min(self.state.u_prev + max_step)
+            .max(self.clamp_lo)
+            .min(self.clamp_hi);
+        self.state.u_prev = total;
+
+        self.state.saturation = self.saturation_of(u, total);
+        if self.state.saturation != Saturation::None {
+            self.state.saturation_count = self.state.saturation_count.saturating_add(1);
+        }
+
+        let output = PidOutput {
+            p: self.state.p,
+            i: self.ki * self.state.i,
+            d: self.kd * self.state.d,
+            total,
+            saturated: self.state.saturation != Saturation::None,
+        };
+
+        if let AntiWindup::BackCalculation { kaw } = self.anti_windup {
+            self.state.i = self.state.i + dt * kaw * (output.total - u);
+        }
+
+        output
+    }
+
+    /// error e as advance() integrates it, after the deadband and direction
+    fn integrand(&self, e: T) -> T {
+        if e.abs() < self.deadband {
+            T::zero()
+        } else {
+            self.signed(e - self.deadband * e.signum())
+        }
+    }
+
+    /// clamps the integral contribution ki * i to the integral limits
+    fn limit_integral(&mut self) {
+        if self.ki == T::zero() {
+            return;
+        }
+        let (lo, hi) = match self.integral_limits {
+            Some(limits) => limits,
+            None => return,
+        };
+        let contribution = self.ki * self.state.i;
+        let limited = contribution.max(lo).min(hi);
+        if limited != contribution {
+            self.state.i = limited / self.ki;
+        }
+    }
+
+    /// saturation of unsaturated output u that was rate limited and then
+    /// clamped to total. a rate limited output counts as saturated towards
+    /// the side it lags, also when the clamp then moves it further.
+    fn saturation_of(&self, u: T, total: T) -> Saturation {
+        if u >= self.clamp_hi || total < u {
+            Saturation::Upper
+        } else if u <= self.clamp_lo || total > u {
+            Saturation::Lower
+        } else {
+            Saturation::None
+        }
+    }
+
+    /// smoothed derivative update, skipped for non-positive dt
+    fn update_derivative(&mut self, de: T, dt: T) {
+        if dt > T::zero() {
+            let de = self.windowed(de);
+            match self.integration {
+                // Tustin discretization of the filtered derivative
+                // s / (tau s + 1). the unfiltered derivative stays a backward
+                // difference, Tustin would make it ring at the Nyquist rate.
+                Integration::Trapezoidal
+                    if self.derivative_tau() > T::zero() && self.derivative_tau().is_finite() =>
+                {
+                    let two_tau = self.derivative_tau() + self.derivative_tau();
+                    self.state.d = ((two_tau - dt) * self.state.d + de + de) / (two_tau + dt);
+                }
+                _ => {
+                    let smooth = self.smooth_at(dt);
+                    self.state.d = smooth * de / dt + (T::one() - smooth) * self.state.d;
+                }
+            }
+        }
+    }
+
+    /// mean change over the derivative window, so that de / dt becomes
+    /// (e[n] - e[n-N]) / (N * dt)
+    fn windowed(&mut self, de: T) -> T {
+        let n = self.derivative_window;
+        if n == 1 {
+            return de;
+        }
+        let pos = self.state.de_pos % n;
+        self.state.de_history[pos] = de;
+        self.state.de_pos = (pos + 1) % n;
+        let sum = self.state.de_history[..n]
+            .iter()
+            .fold(T::zero(), |sum, &de| sum + de);
+        sum / T::from(n).unwrap()
+    }
+
+    /// smoothing coefficient giving the same filter time constant at dt as
+    /// smooth gives at the configured time step
+    fn smooth_at(&self, dt: T) -> T {
+        if dt == self.dt && self.derivative_filter.is_none() {
+            return self.smooth;
+        }
+        dt / (self.derivative_tau() + dt)
+    }
+
+    /// in manual mode, records the output u applied by the operator for error e.
+    /// the integral is back-computed so that the PID terms reproduce u, which
+    /// makes the switch back to Mode::Auto bumpless. requires ki > 0 to track.
+    pub fn track_output(&mut self, u: T, e: T) {
+        self.track(u, e, e, e - self.state.e_prev, self.dt, T::zero());
+    }
+
+    /// holds the output at u in Mode::Manual. the integral is back-computed
+    /// from the current terms so that the output is continuous at the
+    /// transfer, and while manual every step keeps tracking u against the
+    /// new error and returns u. requires ki > 0 to track.
+    pub fn set_manual_output(&mut self, u: T) -> Result<(), PidError> {
+        if u.is_nan() {
+            return Err(PidError::NaN);
+        }
+        let rest = u - self.state.p - self.kd * self.state.d - self.bias;
+        self.shift_integral(rest - self.ki * self.state.i);
+        self.state.u_raw = u;
+        self.state.u_prev = u;
+        self.state.saturation = Saturation::None;
+        self.mode = Mode::Manual;
+        Ok(())
+    }
+
+    /// returns to Mode::Auto. after set_manual_output() or track_output() the
+    /// first automatic output continues from the held one.
+    pub fn set_auto(&mut self) {
+        self.mode = Mode::Auto;
+    }
+
+    /// advances the P and D terms with the error and moves the integral so
+    /// that the terms add up to u
+    fn track(&mut self, u: T, e: T, ep: T, de: T, dt: T, ff: T) {
+        self.update_derivative(self.signed(de), dt);
+        self.state.e_prev2 = self.state.e_prev;
+        self.state.e_prev = e;
+        self.state.p = self.kp * self.signed(ep);
+
+        let rest = u - self.state.p - self.kd * self.state.d - self.bias - ff;
+        self.shift_integral(rest - self.ki * self.state.i);
+
+        self.state.u_raw = u;
+        self.state.u_prev = u;
+        self.state.saturation = Saturation::None;
+    }
+
+    /// clears the accumulated state, keeping dt, gains, clamps and smoothing.
+    pub fn reset(&mut self) {
+        self.state = PidState::default();
+    }
+
+    /// clears only the integral, keeping the derivative history.
+    pub fn reset_integral(&mut self) {
+        self.state.i = T::zero();
+    }
+
+    /// overwrites the accumulated error i, e.g. to preload it from a previous
+    /// session. an escape hatch: the integral contribution stays within
+    /// integral_limits() if set, but nothing else is adjusted, so the next
+    /// output jumps by the change of ki * i.
+    pub fn set_integral(&mut self, i: T) -> Result<(), PidError> {
+        if i.is_nan() {
+            return Err(PidError::NaN);
         }
+        self.state.i = i;
+        self.limit_integral();
+        Ok(())
+    }
+
+    /// snapshot of the dynamic state, e.g. to roll back after an experiment.
+    pub fn state(&self) -> PidState<T> {
+        self.state
+    }
+
+    /// overwrites the dynamic state, keeping the configuration.
+    pub fn restore(&mut self, state: &PidState<T>) {
+        self.state = *state;
+    }
+
+    /// gain setters reject negative, infinite or NaN gains and keep the
+    /// previous value.
+    /// use set_direction() for reverse-acting processes.
+    pub fn set_kp(&mut self, kp: T) -> Result<(), PidError> {
+        self.kp = check_gain(kp)?;
+        Ok(())
+    }
+
+    pub fn set_ki(&mut self, ki: T) -> Result<(), PidError> {
+        self.ki = check_gain(ki)?;
+        Ok(())
+    }
+
+    pub fn set_kd(&mut self, kd: T) -> Result<(), PidError> {
+        self.kd = check_gain(kd)?;
+        Ok(())
+    }
+
+    /// sets all three gains, or none of them if any is invalid.
+    pub fn set_gains(&mut self, kp: T, ki: T, kd: T) -> Result<(), PidError> {
+        let kp = check_gain(kp)?;
+        let ki = check_gain(ki)?;
+        let kd = check_gain(kd)?;
+        self.kp = kp;
+        self.ki = ki;
+        self.kd = kd;
+        Ok(())
+    }
+
+    /// set_gains() from a gain set, e.g. one saved from gains()
+    ///
+    /// ```
+    /// use pid::{PidController, PidGains};
+    ///
+    /// let mut pid = PidController::<f64>::new(0.1, (-1.0, 1.0));
+    /// pid.set_gains(0.5, 0.2, 0.1).unwrap();
+    /// let saved = pid.gains();
+    ///
+    /// let mut other = PidController::<f64>::new(0.1, (-1.0, 1.0));
+    /// other.apply_gains(saved).unwrap();
+    /// assert_eq!(other.gains(), PidGains { kp: 0.5, ki: 0.2, kd: 0.1 });
+    /// ```
+    pub fn apply_gains(&mut self, gains: PidGains<T>) -> Result<(), PidError> {
+        self.set_gains(gains.kp, gains.ki, gains.kd)
+    }
+
+    /// standard (ISA) form: ki = kp / ti, kd = kp * td.
+    /// ti = 0 or T::infinity() disables integral action. the integral is
+    /// rescaled as in set_ki_bumpless().
+    pub fn set_standard_gains(&mut self, kp: T, ti: T, td: T) -> Result<(), PidError> {
+        let kp = check_gain(kp)?;
+        let ti = if ti == T::infinity() {
+            ti
+        } else {
+            check_gain(ti)?
+        };
+        let td = check_gain(td)?;
+        let ki = if ti == T::zero() || ti.is_infinite() {
+            T::zero()
+        } else {
+            kp / ti
+        };
+        let kd = check_gain(kp * td)?;
 
-        self.d = self.smooth * (e - self.e_prev) / self.dt + (1.0 - self.smooth) * self.d;
+        self.kp = kp;
+        self.set_ki_bumpless(ki)?;
+        self.kd = kd;
+        Ok(())
+    }
 
-        let u = self.kp * e + self.ki * self.i + self.kd * self.d;
+    /// sets the derivative smoothing as a first-order filter time constant,
+    /// smooth = dt / (tau + dt). unlike smooth, tau keeps its meaning when dt
+    /// changes. overrides set_smooth() and vice versa.
+    pub fn set_derivative_tau(&mut self, tau: T) -> Result<(), PidError> {
+        let tau = check_time_constant(tau)?;
+        self.smooth = self.dt / (tau + self.dt);
+        self.derivative_filter = None;
+        Ok(())
+    }
 
-        self.unclamped = (self.clamp_lo < u) && (u < self.clamp_hi);
-        self.e_prev = e;
+    /// takes the derivative over the last n steps, (e[n] - e[n-N]) / (N * dt),
+    /// which averages out quantization noise better than smoothing. n = 1,
+    /// the default, is the two-point derivative. n goes up to
+    /// MAX_DERIVATIVE_WINDOW. the smoothing still applies on top. changing
+    /// n clears the window, so the derivative builds up over the next n
+    /// steps.
+    pub fn set_derivative_window(&mut self, n: usize) -> Result<(), PidError> {
+        self.derivative_window = check_window(n)?;
+        self.state.de_history = [T::zero(); MAX_DERIVATIVE_WINDOW];
+        self.state.de_pos = 0;
+        Ok(())
+    }
 
-        u
+    /// set_derivative_tau() by cutoff frequency in rad/s:
+    /// smooth = omega_c * dt / (1 + omega_c * dt). T::infinity() leaves the
+    /// derivative unfiltered.
+    pub fn set_derivative_filter_cutoff(&mut self, omega_c: T) -> Result<(), PidError> {
+        let omega_c = check_time_constant(omega_c)?;
+        self.smooth = self.dt / (omega_c.recip() + self.dt);
+        self.derivative_filter = None;
+        Ok(())
     }
 
-    pub fn set_kp(&mut self, kp: f64) {
-        if kp >= 0.0 {
-            self.kp = kp;
+    /// filters the derivative term as kd * s / (1 + (kd / n) * s), so its
+    /// gain at high frequencies is limited to n whatever kd is. n is
+    /// typically 8 to 20. the time constant kd / n follows later kd changes.
+    /// T::infinity() leaves the derivative unfiltered, like smooth = 1.
+    /// replaces smooth until set_smooth(), set_derivative_tau() or
+    /// set_derivative_filter_cutoff().
+    pub fn set_derivative_filter(&mut self, n: T) -> Result<(), PidError> {
+        let n = check_filter_coefficient(n)?;
+        if n == T::infinity() {
+            self.smooth = T::one();
+            self.derivative_filter = None;
+        } else {
+            self.derivative_filter = Some(n);
         }
+        Ok(())
     }
 
-    pub fn set_ki(&mut self, ki: f64) {
-        if ki >= 0.0 {
-            self.ki = ki;
+    /// bumpless variants: the integral is adjusted so that the output of the
+    /// last step is unchanged under the new gain. this needs ki > 0 for kp and
+    /// kd changes. set_ki_bumpless(0) drops the integral contribution, since
+    /// it cannot be represented without integral gain.
+    pub fn set_kp_bumpless(&mut self, kp: T) -> Result<(), PidError> {
+        let kp = check_gain(kp)?;
+        let ep = if self.kp != T::zero() {
+            self.state.p / self.kp
+        } else {
+            self.signed(self.state.e_prev)
+        };
+        self.shift_integral((self.kp - kp) * ep);
+        self.kp = kp;
+        self.state.p = kp * ep;
+        Ok(())
+    }
+
+    pub fn set_ki_bumpless(&mut self, ki: T) -> Result<(), PidError> {
+        let ki = check_gain(ki)?;
+        self.state.i = if ki == T::zero() {
+            self.state.i
+        } else {
+            self.state.i * self.ki / ki
+        };
+        self.ki = ki;
+        Ok(())
+    }
+
+    pub fn set_kd_bumpless(&mut self, kd: T) -> Result<(), PidError> {
+        let kd = check_gain(kd)?;
+        self.shift_integral((self.kd - kd) * self.state.d);
+        self.kd = kd;
+        Ok(())
+    }
+
+    /// moves du of output into the integral term, if there is one
+    fn shift_integral(&mut self, du: T) {
+        if self.ki != T::zero() {
+            self.state.i = self.state.i + du / self.ki;
         }
     }
 
-    pub fn set_kd(&mut self, kd: f64) {
-        if kd >= 0.0 {
-            self.kd = kd;
+    /// applies the controller direction to an error
+    fn signed(&self, e: T) -> T {
+        match self.direction {
+            ControllerDirection::Direct => e,
+            ControllerDirection::Reverse => -e,
         }
     }
 
-    pub fn set_smooth(&mut self, smooth: f64) {
-        if (0.0..=1.0).contains(&smooth) {
-            self.smooth = smooth;
+    pub fn set_smooth(&mut self, smooth: T) -> Result<(), PidError> {
+        self.smooth = check_smooth(smooth)?;
+        self.derivative_filter = None;
+        Ok(())
+    }
+
+    /// setpoint weight of the proportional term, 1 by default
+    pub fn set_b(&mut self, b: T) -> Result<(), PidError> {
+        self.b = check_weight(b)?;
+        Ok(())
+    }
+
+    /// setpoint weight of the derivative term, 1 by default
+    pub fn set_c(&mut self, c: T) -> Result<(), PidError> {
+        self.c = check_weight(c)?;
+        Ok(())
+    }
+
+    pub fn set_derivative_mode(&mut self, mode: DerivativeMode) {
+        self.derivative_mode = mode;
+    }
+
+    /// in DerivativeMode::OnError, step_with_setpoint() zeroes the
+    /// derivative term in a step in which the setpoint changed, so a
+    /// setpoint step does not kick the output. the smoothing and the
+    /// derivative window restart from zero after it. a lighter alternative to
+    /// set_c(0) or DerivativeMode::OnMeasurement. off by default. a ramped
+    /// setpoint in update() changes every step, which skips the derivative
+    /// for the whole ramp. the first step after new() or reset() counts as a
+    /// change, since there is no previous setpoint.
+    pub fn set_prevent_derivative_kick(&mut self, enabled: bool) {
+        self.prevent_derivative_kick = enabled;
+    }
+
+    pub fn set_direction(&mut self, direction: ControllerDirection) {
+        self.direction = direction;
+    }
+
+    /// ConditionalIntegration keeps integrating towards leaving the bound
+    /// and is the default. Clamp is simpler but also holds the integrator
+    /// while it would help, BackCalculation unwinds fastest but needs kaw
+    /// tuned, and None leaves the windup unchecked. see AntiWindup.
+    pub fn set_anti_windup(&mut self, anti_windup: AntiWindup<T>) -> Result<(), PidError> {
+        self.anti_windup = check_anti_windup(anti_windup)?;
+        Ok(())
+    }
+
+    /// switches to back-calculation anti-windup with tracking gain kaw, which
+    /// must be finite and not negative.
+    pub fn set_kaw(&mut self, kaw: T) -> Result<(), PidError> {
+        self.set_anti_windup(AntiWindup::BackCalculation { kaw })
+    }
+
+    /// limits how fast the output may change, in output units per unit time.
+    /// T::infinity() disables the limit, which is the default. While the
+    /// limit holds the output back, anti-windup treats it as saturated.
+    pub fn set_output_rate_limit(&mut self, max_rate: T) -> Result<(), PidError> {
+        self.max_rate = check_rate_limit(max_rate)?;
+        Ok(())
+    }
+
+    /// switches to back-calculation anti-windup with tracking time constant
+    /// t_t: the integral contribution ki * i closes the gap to the clamped
+    /// output within about t_t. the feedback enters the accumulator, so this
+    /// sets kaw = 1 / (ki * t_t) from the current ki. set ki first and call
+    /// this again after changing it. with ki = 0 there is no integral to
+    /// track and PidError::InfiniteGain is returned.
+    pub fn set_anti_windup_tracking_constant(&mut self, t_t: T) -> Result<(), PidError> {
+        let t_t = check_time_constant(t_t)?;
+        self.set_kaw((self.ki * t_t).recip())
+    }
+
+    /// constant offset added to every output before saturation, e.g. the
+    /// nominal actuator position. like feedforward it bypasses the integrator.
+    pub fn set_output_bias(&mut self, bias: T) -> Result<(), PidError> {
+        if bias.is_nan() {
+            return Err(PidError::NaN);
         }
+        self.bias = bias;
+        Ok(())
     }
 
-    pub fn dt(&self) -> f64 {
+    /// integral separation: the integrator only accumulates while
+    /// |e| <= threshold. T::infinity() disables it, which is the default.
+    pub fn set_integral_separation_threshold(&mut self, threshold: T) -> Result<(), PidError> {
+        self.integral_threshold = check_threshold(threshold)?;
+        Ok(())
+    }
+
+    /// errors with |e| < width are treated as zero by P and I: the
+    /// proportional term gets no error input and the integrator holds, so
+    /// the output holds its last value. the derivative still sees the true
+    /// error, so it can damp motion into the band. larger errors reach P and
+    /// I reduced by width, which keeps the output continuous when the error
+    /// enters or leaves the band, also with a setpoint weight b < 1.
+    /// 0 disables it, which is the default.
+    pub fn set_deadband(&mut self, width: T) -> Result<(), PidError> {
+        self.deadband = check_threshold(width)?;
+        Ok(())
+    }
+
+    /// integrator leak rate, per unit time: every step scales i by
+    /// exp(-leak * dt), about (1 - leak * dt) for small leak * dt, before the
+    /// new error is added, so old errors fade out. the factor stays within
+    /// [0,1] for any dt, so a long step only decays i further. the decay
+    /// also applies while anti-windup holds the integrator. 0 disables it,
+    /// which is the default. negative or infinite rates are rejected.
+    pub fn set_leak(&mut self, leak: T) -> Result<(), PidError> {
+        self.leak = check_leak(leak)?;
+        Ok(())
+    }
+
+    /// set_leak() by time constant: leak = 1 / tau, so every step scales i
+    /// by exp(-dt / tau) and without new error i decays like exp(-t / tau).
+    /// any positive tau works at any dt. T::infinity() disables the leak.
+    pub fn set_integral_leak(&mut self, tau: T) -> Result<(), PidError> {
+        let tau = check_time_constant(tau)?;
+        self.set_leak(tau.recip())
+    }
+
+    pub fn set_setpoint(&mut self, setpoint: T) -> Result<(), PidError> {
+        if setpoint.is_nan() {
+            return Err(PidError::NaN);
+        }
+        self.setpoint = setpoint;
+        Ok(())
+    }
+
+    /// limits how fast update() moves its effective setpoint towards
+    /// setpoint(), in setpoint units per unit time, the same way up and down.
+    /// a new setpoint mid-ramp redirects from the current effective value.
+    /// T::infinity() disables the ramp, which is the default.
+    /// step_with_setpoint() is not ramped.
+    pub fn set_setpoint_ramp(&mut self, max_rate: T) -> Result<(), PidError> {
+        self.set_setpoint_ramp_rates(max_rate, max_rate)
+    }
+
+    /// set_setpoint_ramp() with separate rates for rising and falling
+    /// setpoints
+    pub fn set_setpoint_ramp_rates(&mut self, up: T, down: T) -> Result<(), PidError> {
+        let up = check_rate_limit(up)?;
+        let down = check_rate_limit(down)?;
+        self.setpoint_ramp_up = up;
+        self.setpoint_ramp_down = down;
+        Ok(())
+    }
+
+    /// changes the output limits, rejecting NaN or clamp.0 >= clamp.1.
+    /// the saturation of the last output is re-evaluated against the new
+    /// limits, so anti-windup acts on them from the next step. the last
+    /// output is moved into the new range, which is where a rate limit
+    /// continues from.
+    pub fn set_clamp(&mut self, clamp: (T, T)) -> Result<(), PidError> {
+        let (lo, hi) = check_clamp(clamp.0, clamp.1)?;
+        self.clamp_lo = lo;
+        self.clamp_hi = hi;
+        self.state.u_prev = self.state.u_prev.max(lo).min(hi);
+        self.state.saturation = self.saturation_of(self.state.u_raw, self.state.u_raw);
+        Ok(())
+    }
+
+    /// bounds the integral contribution ki * i, independently of the output
+    /// clamp. the accumulator is clamped with it, so changing ki does not
+    /// change the effective limit. by default the integral is only bounded by
+    /// the anti-windup.
+    pub fn set_integral_limits(&mut self, lo: T, hi: T) -> Result<(), PidError> {
+        self.integral_limits = Some(check_clamp(lo, hi)?);
+        Ok(())
+    }
+
+    /// low-pass filters the error before all three terms, unlike smooth which
+    /// only affects the derivative. alpha in [0,1], 1 (the default) disables
+    /// it. a measurement derivative in DerivativeMode::OnMeasurement is not
+    /// filtered.
+    pub fn set_input_filter(&mut self, alpha: T) -> Result<(), PidError> {
+        self.input_filter = check_smooth(alpha)?;
+        Ok(())
+    }
+
+    pub fn set_nan_policy(&mut self, policy: NanPolicy) {
+        self.nan_policy = policy;
+    }
+
+    /// selects forward Euler or trapezoidal integration, see Integration.
+    /// the change applies from the next step.
+    pub fn set_integration_method(&mut self, method: Integration) {
+        self.integration = method;
+    }
+
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    pub fn dt(&self) -> T {
         self.dt
     }
 
-    pub fn kp(&self) -> f64 {
+    pub fn kp(&self) -> T {
         self.kp
     }
 
-    pub fn ki(&self) -> f64 {
+    pub fn ki(&self) -> T {
         self.ki
     }
 
-    pub fn clamp_lo(&self) -> f64 {
+    pub fn kd(&self) -> T {
+        self.kd
+    }
+
+    /// kp, ki and kd as one value
+    pub fn gains(&self) -> PidGains<T> {
+        PidGains {
+            kp: self.kp,
+            ki: self.ki,
+            kd: self.kd,
+        }
+    }
+
+    /// integral time of the standard form, T::infinity() without integral action
+    pub fn ti(&self) -> T {
+        if self.ki == T::zero() {
+            T::infinity()
+        } else {
+            self.kp / self.ki
+        }
+    }
+
+    /// derivative time of the standard form
+    pub fn td(&self) -> T {
+        if self.kd == T::zero() {
+            T::zero()
+        } else {
+            self.kd / self.kp
+        }
+    }
+
+    pub fn clamp_lo(&self) -> T {
         self.clamp_lo
     }
 
-    pub fn clamp_hi(&self) -> f64 {
+    pub fn clamp_hi(&self) -> T {
         self.clamp_hi
     }
 
-    pub fn smooth(&self) -> f64 {
+    pub fn smooth(&self) -> T {
         self.smooth
     }
 
-    pub fn p(&self) -> f64 {
-        self.p
+    pub fn prevent_derivative_kick(&self) -> bool {
+        self.prevent_derivative_kick
+    }
+
+    pub fn derivative_window(&self) -> usize {
+        self.derivative_window
+    }
+
+    pub fn derivative_mode(&self) -> DerivativeMode {
+        self.derivative_mode
+    }
+
+    pub fn b(&self) -> T {
+        self.b
+    }
+
+    pub fn c(&self) -> T {
+        self.c
     }
 
-    pub fn i(&self) -> f64 {
-        self.i
+    pub fn direction(&self) -> ControllerDirection {
+        self.direction
     }
 
-    pub fn d(&self) -> f64 {
-        self.d
+    pub fn anti_windup(&self) -> AntiWindup<T> {
+        self.anti_windup
+    }
+
+    pub fn output_rate_limit(&self) -> T {
+        self.max_rate
+    }
+
+    pub fn output_bias(&self) -> T {
+        self.bias
+    }
+
+    pub fn integral_separation_threshold(&self) -> T {
+        self.integral_threshold
+    }
+
+    pub fn deadband(&self) -> T {
+        self.deadband
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn nan_policy(&self) -> NanPolicy {
+        self.nan_policy
+    }
+
+    pub fn integration_method(&self) -> Integration {
+        self.integration
+    }
+
+    pub fn setpoint(&self) -> T {
+        self.setpoint
+    }
+
+    /// (up, down) rates of the setpoint ramp
+    pub fn setpoint_ramp_rates(&self) -> (T, T) {
+        (self.setpoint_ramp_up, self.setpoint_ramp_down)
+    }
+
+    /// setpoint the last update() controlled to, setpoint() before the
+    /// first one
+    pub fn effective_setpoint(&self) -> T {
+        self.state.r_ramped.unwrap_or(self.setpoint)
+    }
+
+    pub fn input_filter(&self) -> T {
+        self.input_filter
+    }
+
+    /// bounds set by set_integral_limits(), None if unbounded
+    pub fn integral_limits(&self) -> Option<(T, T)> {
+        self.integral_limits
+    }
+
+    pub fn leak(&self) -> T {
+        self.leak
+    }
+
+    /// filter time constant of the derivative, kd / n with a derivative
+    /// filter, otherwise equivalent to smooth. 0 when filtering is off.
+    pub fn derivative_tau(&self) -> T {
+        match self.derivative_filter {
+            Some(n) => self.kd / n,
+            None => self.dt * (T::one() - self.smooth) / self.smooth,
+        }
+    }
+
+    /// filter coefficient n set by set_derivative_filter()
+    pub fn derivative_filter(&self) -> Option<T> {
+        self.derivative_filter
+    }
+
+    /// proportional contribution of the last step
+    pub fn p(&self) -> T {
+        self.state.p
+    }
+
+    /// accumulated error, not scaled by ki
+    pub fn i(&self) -> T {
+        self.state.i
+    }
+
+    /// smoothed derivative of the error, not scaled by kd
+    pub fn d(&self) -> T {
+        self.state.d
+    }
+
+    /// integral contribution ki * i
+    pub fn i_contribution(&self) -> T {
+        self.ki * self.state.i
+    }
+
+    /// derivative contribution kd * d
+    pub fn d_contribution(&self) -> T {
+        self.kd * self.state.d
     }
 
     pub fn unclamped(&self) -> bool {
-        self.unclamped
+        self.state.saturation == Saturation::None
+    }
+
+    pub fn saturation(&self) -> Saturation {
+        self.state.saturation
+    }
+
+    /// whether the last output was held at a clamp bound or by the rate
+    /// limit, the opposite of unclamped()
+    pub fn is_saturated(&self) -> bool {
+        !self.unclamped()
+    }
+
+    /// steps since the last reset() whose output was saturated. a count
+    /// growing with every step points at undersized actuation or a tuning
+    /// that asks for more than the clamp allows.
+    ///
+    /// ```
+    /// use pid::PidController;
+    ///
+    /// let mut pid = PidController::<f64>::new(0.1, (-1.0, 1.0));
+    /// pid.set_kp(2.0).unwrap();
+    /// for _ in 0..5 {
+    ///     pid.step(1.0);
+    ///     assert!(pid.is_saturated());
+    /// }
+    /// pid.step(0.25);
+    /// assert!(!pid.is_saturated());
+    /// assert_eq!(pid.saturation_count(), 5);
+    ///
+    /// pid.reset();
+    /// assert_eq!(pid.saturation_count(), 0);
+    /// ```
+    pub fn saturation_count(&self) -> u64 {
+        self.state.saturation_count
+    }
+
+    /// unsaturated output of the last step
+    pub fn raw_output(&self) -> T {
+        self.state.u_raw
+    }
+
+    pub fn e_prev(&self) -> T {
+        self.state.e_prev
+    }
+
+    pub fn y_prev(&self) -> T {
+        self.state.y_prev
+    }
+
+    pub fn r_prev(&self) -> T {
+        self.state.r_prev
+    }
+}
+
+/// dt = 1 with zero gains, no smoothing and no clamping. dt scales the
+/// integral and derivative terms, so set it before using ki or kd.
+impl<T: Float> Default for PidController<T> {
+    fn default() -> Self {
+        Self::new(T::one(), (T::neg_infinity(), T::infinity()))
+    }
+}
+
+fn check_dt<T: Float>(dt: T) -> Result<T, PidError> {
+    if dt.is_nan() {
+        Err(PidError::NaN)
+    } else if dt <= T::zero() {
+        Err(PidError::NonPositiveDt)
+    } else {
+        Ok(dt)
+    }
+}
+
+fn check_time_constant<T: Float>(tau: T) -> Result<T, PidError> {
+    if tau.is_nan() {
+        Err(PidError::NaN)
+    } else if tau <= T::zero() {
+        Err(PidError::NonPositiveTimeConstant)
+    } else {
+        Ok(tau)
+    }
+}
+
+fn check_clamp<T: Float>(lo: T, hi: T) -> Result<(T, T), PidError> {
+    if lo.is_nan() || hi.is_nan() {
+        Err(PidError::NaN)
+    } else if lo >= hi {
+        Err(PidError::InvertedClamp)
+    } else {
+        Ok((lo, hi))
+    }
+}
+
+fn check_gain<T: Float>(gain: T) -> Result<T, PidError> {
+    if gain.is_nan() {
+        Err(PidError::NaN)
+    } else if gain < T::zero() {
+        Err(PidError::NegativeGain)
+    } else if gain.is_infinite() {
+        Err(PidError::InfiniteGain)
+    } else {
+        Ok(gain)
+    }
+}
+
+fn check_anti_windup<T: Float>(anti_windup: AntiWindup<T>) -> Result<AntiWindup<T>, PidError> {
+    if let AntiWindup::BackCalculation { kaw } = anti_windup {
+        check_gain(kaw)?;
+    }
+    Ok(anti_windup)
+}
+
+fn check_rate_limit<T: Float>(max_rate: T) -> Result<T, PidError> {
+    if max_rate.is_nan() {
+        Err(PidError::NaN)
+    } else if max_rate <= T::zero() {
+        Err(PidError::NonPositiveRateLimit)
+    } else {
+        Ok(max_rate)
+    }
+}
+
+fn check_weight<T: Float>(weight: T) -> Result<T, PidError> {
+    if weight.is_nan() {
+        Err(PidError::NaN)
+    } else if weight < T::zero() || weight > T::one() {
+        Err(PidError::WeightOutOfRange)
+    } else {
+        Ok(weight)
     }
+}
+
+fn check_threshold<T: Float>(threshold: T) -> Result<T, PidError> {
+    if threshold.is_nan() {
+        Err(PidError::NaN)
+    } else if threshold < T::zero() {
+        Err(PidError::NegativeThreshold)
+    } else {
+        Ok(threshold)
+    }
+}
+
+fn check_leak<T: Float>(leak: T) -> Result<T, PidError> {
+    if leak.is_nan() {
+        Err(PidError::NaN)
+    } else if leak < T::zero() || leak == T::infinity() {
+        Err(PidError::LeakOutOfRange)
+    } else {
+        Ok(leak)
+    }
+}
+
+fn check_filter_coefficient<T: Float>(n: T) -> Result<T, PidError> {
+    if n.is_nan() {
+        Err(PidError::NaN)
+    } else if n <= T::zero() {
+        Err(PidError::NonPositiveFilterCoefficient)
+    } else {
+        Ok(n)
+    }
+}
+
+fn check_window(n: usize) -> Result<usize, PidError> {
+    if n == 0 || n > MAX_DERIVATIVE_WINDOW {
+        Err(PidError::WindowOutOfRange)
+    } else {
+        Ok(n)
+    }
+}
 
-    pub fn e_prev(&self) -> f64 {
-        self.e_prev
+fn check_smooth<T: Float>(smooth: T) -> Result<T, PidError> {
+    if smooth.is_nan() {
+        Err(PidError::NaN)
+    } else if smooth < T::zero() || smooth > T::one() {
+        Err(PidError::SmoothOutOfRange)
+    } else {
+        Ok(smooth)
     }
 }
@@ -0,0 +1,255 @@
+use crate::{check_gain, PidController, PidError, PidGains};
+
+use num_traits::Float;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// gains shared with other threads, e.g. a tuning UI, without locking the
+/// control loop.
+///
+/// writers take a mutex among themselves, the control thread only reads:
+/// a sequence counter around the gain fields lets it detect a write in
+/// progress and skip the update instead of waiting or seeing a mix of old
+/// and new gains.
+///
+/// ```
+/// use pid::{PidController, PidGains, PidParams};
+/// use std::sync::Arc;
+///
+/// let params = Arc::new(PidParams::new(PidGains { kp: 1.0, ki: 2.0, kd: 3.0 }).unwrap());
+/// let writer = {
+///     let params = Arc::clone(&params);
+///     std::thread::spawn(move || {
+///         for k in 1..1000 {
+///             let kp = k as f64;
+///             params.set_gains(PidGains { kp, ki: 2.0 * kp, kd: 3.0 * kp }).unwrap();
+///         }
+///     })
+/// };
+///
+/// let mut pid = PidController::<f64>::new(0.001, (-1.0, 1.0));
+/// for _ in 0..1000 {
+///     pid.sync_params(&params);
+///     assert_eq!(pid.ki(), 2.0 * pid.kp());
+///     assert_eq!(pid.kd(), 3.0 * pid.kp());
+///     pid.step(0.1);
+/// }
+/// writer.join().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct PidParams {
+    /// even while the gains are consistent, odd during a write
+    seq: AtomicU64,
+    kp: AtomicU64,
+    ki: AtomicU64,
+    kd: AtomicU64,
+    writer: Mutex<()>,
+}
+
+impl PidParams {
+    pub fn new(gains: PidGains<f64>) -> Result<Self, PidError> {
+        Ok(Self {
+            seq: AtomicU64::new(0),
+            kp: AtomicU64::new(check_gain(gains.kp)?.to_bits()),
+            ki: AtomicU64::new(check_gain(gains.ki)?.to_bits()),
+            kd: AtomicU64::new(check_gain(gains.kd)?.to_bits()),
+            writer: Mutex::new(()),
+        })
+    }
+
+    /// publishes all three gains at once. rejects negative or NaN gains and
+    /// keeps the previous ones.
+    pub fn set_gains(&self, gains: PidGains<f64>) -> Result<(), PidError> {
+        let (kp, ki, kd) = (
+            check_gain(gains.kp)?,
+            check_gain(gains.ki)?,
+            check_gain(gains.kd)?,
+        );
+        let _guard = self.lock();
+        self.write(kp, ki, kd);
+        Ok(())
+    }
+
+    pub fn set_kp(&self, kp: f64) -> Result<(), PidError> {
+        self.update(|gains| gains.kp = kp)
+    }
+
+    pub fn set_ki(&self, ki: f64) -> Result<(), PidError> {
+        self.update(|gains| gains.ki = ki)
+    }
+
+    pub fn set_kd(&self, kd: f64) -> Result<(), PidError> {
+        self.update(|gains| gains.kd = kd)
+    }
+
+    /// changes some of the gains under the writer lock
+    fn update(&self, change: impl FnOnce(&mut PidGains<f64>)) -> Result<(), PidError> {
+        let _guard = self.lock();
+        let mut gains = self.read();
+        change(&mut gains);
+        let (kp, ki, kd) = (
+            check_gain(gains.kp)?,
+            check_gain(gains.ki)?,
+            check_gain(gains.kd)?,
+        );
+        self.write(kp, ki, kd);
+        Ok(())
+    }
+
+    /// the gains only change with the lock held, a panicking writer cannot
+    /// leave them half written
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// publishes validated gains, with the writer lock held
+    fn write(&self, kp: f64, ki: f64, kd: f64) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.seq.store(seq + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        self.kp.store(kp.to_bits(), Ordering::Relaxed);
+        self.ki.store(ki.to_bits(), Ordering::Relaxed);
+        self.kd.store(kd.to_bits(), Ordering::Relaxed);
+        self.seq.store(seq + 2, Ordering::Release);
+    }
+
+    /// a consistent snapshot, None if a write was in progress
+    pub fn try_gains(&self) -> Option<PidGains<f64>> {
+        let before = self.seq.load(Ordering::Acquire);
+        if before % 2 == 1 {
+            return None;
+        }
+        let gains = self.read();
+        fence(Ordering::Acquire);
+        if self.seq.load(Ordering::Relaxed) == before {
+            Some(gains)
+        } else {
+            None
+        }
+    }
+
+    /// the current gains, retrying until no write is in progress
+    pub fn gains(&self) -> PidGains<f64> {
+        loop {
+            if let Some(gains) = self.try_gains() {
+                return gains;
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    fn read(&self) -> PidGains<f64> {
+        PidGains {
+            kp: f64::from_bits(self.kp.load(Ordering::Relaxed)),
+            ki: f64::from_bits(self.ki.load(Ordering::Relaxed)),
+            kd: f64::from_bits(self.kd.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl<T: Float> PidController<T> {
+    /// takes over the gains published in params, ki bumplessly as in
+    /// set_ki_bumpless(). call it once per step from the control thread: it
+    /// never blocks, and if a write is in progress it leaves the gains
+    /// unchanged until the next call. returns whether the gains were read.
+    pub fn sync_params(&mut self, params: &PidParams) -> bool {
+        let gains = match params.try_gains() {
+            Some(gains) => gains,
+            None => return false,
+        };
+        let (kp, ki, kd) = match (T::from(gains.kp), T::from(gains.ki), T::from(gains.kd)) {
+            (Some(kp), Some(ki), Some(kd)) => (kp, ki, kd),
+            _ => return false,
+        };
+        // the handle only publishes valid gains, the setters cannot fail
+        let _ = self.set_kp(kp);
+        if ki != self.ki() {
+            let _ = self.set_ki_bumpless(ki);
+        }
+        let _ = self.set_kd(kd);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn readers_never_see_a_mix_of_writes() {
+        let params = Arc::new(
+            PidParams::new(PidGains {
+                kp: 1.0,
+                ki: 2.0,
+                kd: 3.0,
+            })
+            .unwrap(),
+        );
+        let writers: std::vec::Vec<_> = (0..2)
+            .map(|w| {
+                let params = Arc::clone(&params);
+                thread::spawn(move || {
+                    for k in 1..20_000 {
+                        let kp = (2 * k + w) as f64;
+                        params
+                            .set_gains(PidGains {
+                                kp,
+                                ki: 2.0 * kp,
+                                kd: 3.0 * kp,
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        let mut pid = PidController::<f64>::new(0.001, (-1.0, 1.0));
+        let mut synced = 0;
+        for _ in 0..20_000 {
+            if let Some(gains) = params.try_gains() {
+                assert_eq!(gains.ki, 2.0 * gains.kp);
+                assert_eq!(gains.kd, 3.0 * gains.kp);
+            }
+            synced += pid.sync_params(&params) as usize;
+            assert_eq!(pid.ki(), 2.0 * pid.kp());
+            assert_eq!(pid.kd(), 3.0 * pid.kp());
+            assert!(pid.step(0.1).is_finite());
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert!(synced > 0);
+        assert_eq!(params.gains().ki, 2.0 * params.gains().kp);
+    }
+
+    #[test]
+    fn invalid_gains_keep_the_published_ones() {
+        let params = PidParams::new(PidGains {
+            kp: 1.0,
+            ki: 2.0,
+            kd: 3.0,
+        })
+        .unwrap();
+        assert_eq!(params.set_kp(-1.0), Err(PidError::NegativeGain));
+        assert_eq!(
+            params.set_gains(PidGains {
+                kp: 1.0,
+                ki: f64::NAN,
+                kd: 0.0
+            }),
+            Err(PidError::NaN)
+        );
+        params.set_kd(4.0).unwrap();
+        assert_eq!(
+            params.gains(),
+            PidGains {
+                kp: 1.0,
+                ki: 2.0,
+                kd: 4.0
+            }
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use crate::{check_gain, PidController, PidError, PidGains};
+
+use alloc::vec::Vec;
+use num_traits::Float;
+
+/// gains as a function of an operating point, linearly interpolated
+/// between breakpoints.
+///
+/// below the first and above the last breakpoint the end gains hold.
+///
+/// ```
+/// use pid::{GainSchedule, PidController, PidGains};
+///
+/// let mut schedule = GainSchedule::<f64>::new();
+/// schedule.add(0.0, PidGains { kp: 1.0, ki: 0.1, kd: 0.0 }).unwrap();
+/// schedule.add(10.0, PidGains { kp: 3.0, ki: 0.3, kd: 0.2 }).unwrap();
+///
+/// let gains = schedule.gains(5.0).unwrap();
+/// assert!((gains.kp - 2.0).abs() < 1e-12);
+/// assert!((gains.ki - 0.2).abs() < 1e-12);
+/// assert!((gains.kd - 0.1).abs() < 1e-12);
+/// assert_eq!(schedule.gains(20.0), Some(PidGains { kp: 3.0, ki: 0.3, kd: 0.2 }));
+///
+/// let mut pid = PidController::<f64>::new(1.0, (-1.0, 1.0));
+/// pid.apply_schedule(&schedule, 5.0).unwrap();
+/// assert!((pid.kp() - 2.0).abs() < 1e-12);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct GainSchedule<T = f64> {
+    /// (operating point, gains), sorted by operating point
+    breakpoints: Vec<(T, PidGains<T>)>,
+}
+
+impl<T: Float> GainSchedule<T> {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+        }
+    }
+
+    /// adds a breakpoint, replacing one at the same operating point
+    pub fn add(&mut self, x: T, gains: PidGains<T>) -> Result<(), PidError> {
+        if x.is_nan() {
+            return Err(PidError::NaN);
+        }
+        let gains = PidGains {
+            kp: check_gain(gains.kp)?,
+            ki: check_gain(gains.ki)?,
+            kd: check_gain(gains.kd)?,
+        };
+        let at = self.breakpoints.partition_point(|&(bx, _)| bx < x);
+        match self.breakpoints.get_mut(at) {
+            Some(bp) if bp.0 == x => bp.1 = gains,
+            _ => self.breakpoints.insert(at, (x, gains)),
+        }
+        Ok(())
+    }
+
+    pub fn breakpoints(&self) -> &[(T, PidGains<T>)] {
+        &self.breakpoints
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    /// gains at operating point x.
+    /// None if the schedule is empty or x is NaN.
+    pub fn gains(&self, x: T) -> Option<PidGains<T>> {
+        let (first, last) = (self.breakpoints.first()?, self.breakpoints.last()?);
+        if x.is_nan() {
+            return None;
+        }
+        if x <= first.0 {
+            return Some(first.1);
+        }
+        if x >= last.0 {
+            return Some(last.1);
+        }
+
+        let at = self.breakpoints.partition_point(|&(bx, _)| bx <= x);
+        let (x0, g0) = self.breakpoints[at - 1];
+        let (x1, g1) = self.breakpoints[at];
+        let t = (x - x0) / (x1 - x0);
+        let lerp = |a: T, b: T| a + (b - a) * t;
+        Some(PidGains {
+            kp: lerp(g0.kp, g1.kp),
+            ki: lerp(g0.ki, g1.ki),
+            kd: lerp(g0.kd, g1.kd),
+        })
+    }
+}
+
+impl<T: Float> Default for GainSchedule<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Float> PidController<T> {
+    /// sets the gains scheduled for operating_point. ki changes as in
+    /// set_ki_bumpless(), so calling this every step does not kick the
+    /// output. an empty schedule leaves the gains unchanged.
+    pub fn apply_schedule(
+        &mut self,
+        schedule: &GainSchedule<T>,
+        operating_point: T,
+    ) -> Result<(), PidError> {
+        if operating_point.is_nan() {
+            return Err(PidError::NaN);
+        }
+        if let Some(gains) = schedule.gains(operating_point) {
+            self.set_kp(gains.kp)?;
+            self.set_ki_bumpless(gains.ki)?;
+            self.set_kd(gains.kd)?;
+        }
+        Ok(())
+    }
+}
+
+/// switches between whole controllers by operating region.
+///
+/// each controller covers the operating points up to its upper bound and
+/// above the previous one, the last one also everything above. on a
+/// region change the incoming controller takes over the state of the
+/// outgoing one, with the integral rescaled so that ki * i carries over,
+/// which avoids a transient at the switch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GainScheduledController<T = f64> {
+    /// (upper bound, controller), sorted by upper bound
+    regions: Vec<(T, PidController<T>)>,
+
+    /// index of the region of the last step
+    active: Option<usize>,
+}
+
+impl<T: Float> GainScheduledController<T> {
+    pub fn new() -> Self {
+        Self {
+            regions: Vec::new(),
+            active: None,
+        }
+    }
+
+    /// adds a region covering operating points up to upper_bound
+    pub fn add_region(
+        &mut self,
+        upper_bound: T,
+        controller: PidController<T>,
+    ) -> Result<(), PidError> {
+        if upper_bound.is_nan() {
+            return Err(PidError::NaN);
+        }
+        let at = self.regions.partition_point(|&(b, _)| b <= upper_bound);
+        self.regions.insert(at, (upper_bound, controller));
+        if let Some(active) = self.active.as_mut() {
+            if *active >= at {
+                *active += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// steps the controller of the region scheduling_variable falls in.
+    /// a NaN scheduling_variable stays in the current region.
+    /// None while no region has been added.
+    pub fn step_scheduled(&mut self, e: T, scheduling_variable: T) -> Option<T> {
+        let last = self.regions.len().checked_sub(1)?;
+        let index = if scheduling_variable.is_nan() {
+            self.active.unwrap_or(last)
+        } else {
+            self.regions
+                .partition_point(|&(b, _)| b < scheduling_variable)
+                .min(last)
+        };
+
+        if let Some(previous) = self.active.filter(|&previous| previous != index) {
+            let outgoing = self.regions[previous].1;
+            let incoming = &mut self.regions[index].1;
+            let mut state = outgoing.state();
+            if incoming.ki() != T::zero() {
+                state.i = outgoing.ki() * state.i / incoming.ki();
+            }
+            incoming.restore(&state);
+        }
+        self.active = Some(index);
+
+        Some(self.regions[index].1.step(e))
+    }
+
+    pub fn regions(&self) -> &[(T, PidController<T>)] {
+        &self.regions
+    }
+
+    /// controller of the index-th region, e.g. for tuning
+    pub fn region_mut(&mut self, index: usize) -> Option<&mut PidController<T>> {
+        self.regions.get_mut(index).map(|(_, pid)| pid)
+    }
+
+    /// index of the region the last step ran in
+    pub fn active_region(&self) -> Option<usize> {
+        self.active
+    }
+
+    /// clears the state of every region
+    pub fn reset(&mut self) {
+        for (_, pid) in &mut self.regions {
+            pid.reset();
+        }
+        self.active = None;
+    }
+}
+
+impl<T: Float> Default for GainScheduledController<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gains(kp: f64, ki: f64, kd: f64) -> PidGains<f64> {
+        PidGains { kp, ki, kd }
+    }
+
+    #[test]
+    fn interpolates_between_breakpoints() {
+        let mut schedule = GainSchedule::new();
+        assert_eq!(schedule.gains(0.0), None);
+        schedule.add(10.0, gains(3.0, 1.0, 0.0)).unwrap();
+        schedule.add(0.0, gains(1.0, 0.5, 0.0)).unwrap();
+        assert_eq!(schedule.breakpoints()[0].0, 0.0);
+
+        assert_eq!(schedule.gains(-5.0), Some(gains(1.0, 0.5, 0.0)));
+        assert_eq!(schedule.gains(2.5), Some(gains(1.5, 0.625, 0.0)));
+        assert_eq!(schedule.gains(15.0), Some(gains(3.0, 1.0, 0.0)));
+        assert_eq!(schedule.gains(f64::NAN), None);
+
+        schedule.add(10.0, gains(5.0, 1.0, 0.0)).unwrap();
+        assert_eq!(schedule.breakpoints().len(), 2);
+        assert_eq!(schedule.gains(5.0), Some(gains(3.0, 0.75, 0.0)));
+        assert_eq!(
+            schedule.add(5.0, gains(-1.0, 0.0, 0.0)),
+            Err(PidError::NegativeGain)
+        );
+    }
+
+    #[test]
+    fn apply_schedule_keeps_the_integral_contribution() {
+        let mut schedule = GainSchedule::new();
+        schedule.add(0.0, gains(1.0, 1.0, 0.0)).unwrap();
+        schedule.add(1.0, gains(1.0, 4.0, 0.0)).unwrap();
+        let mut pid = PidController::new(0.1, (-10.0, 10.0));
+        pid.apply_schedule(&schedule, 0.0).unwrap();
+        for _ in 0..10 {
+            pid.step(1.0);
+        }
+        let contribution = pid.i_contribution();
+        pid.apply_schedule(&schedule, 1.0).unwrap();
+        assert_eq!(pid.gains(), gains(1.0, 4.0, 0.0));
+        assert!((pid.i_contribution() - contribution).abs() < 1e-12);
+    }
+
+    #[test]
+    fn switching_regions_carries_the_integral_contribution() {
+        let mut low = PidController::new(0.1, (-10.0, 10.0));
+        low.set_gains(1.0, 1.0, 0.0).unwrap();
+        let mut high = low;
+        high.set_ki(4.0).unwrap();
+        let mut scheduled = GainScheduledController::new();
+        assert_eq!(scheduled.step_scheduled(1.0, 0.0), None);
+        scheduled.add_region(5.0, high).unwrap();
+        scheduled.add_region(1.0, low).unwrap();
+        assert_eq!(scheduled.add_region(f64::NAN, low), Err(PidError::NaN));
+
+        for _ in 0..10 {
+            scheduled.step_scheduled(1.0, 0.5);
+        }
+        assert_eq!(scheduled.active_region(), Some(0));
+        let contribution = scheduled.regions()[0].1.i_contribution();
+
+        scheduled.step_scheduled(0.0, 20.0);
+        assert_eq!(scheduled.active_region(), Some(1));
+        let high = &scheduled.regions()[1].1;
+        assert!((high.i_contribution() - contribution).abs() < 1e-12);
+        // a NaN operating point stays in the current region
+        scheduled.step_scheduled(0.0, f64::NAN);
+        assert_eq!(scheduled.active_region(), Some(1));
+
+        scheduled.reset();
+        assert_eq!(scheduled.active_region(), None);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::PidController;
+
+use num_traits::Float;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+impl<T: Float + Serialize> Serialize for PidController<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PidController::serialize(self, serializer)
+    }
+}
+
+impl<'de, T: Float + Deserialize<'de>> Deserialize<'de> for PidController<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pid = PidController::deserialize(deserializer)?;
+        pid.validate().map_err(de::Error::custom)?;
+        Ok(pid)
+    }
+}
+
+/// settings that are infinite when disabled or unbounded, written as None
+/// since formats like JSON have no infinity
+pub(crate) mod unbounded {
+    use super::*;
+
+    pub fn serialize<T: Float + Serialize, S: Serializer>(
+        x: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        finite_or_none(*x, T::infinity()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: Float + Deserialize<'de>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        Ok(Option::deserialize(deserializer)?.unwrap_or_else(T::infinity))
+    }
+}
+
+/// lower bounds, negative infinity written as None
+pub(crate) mod unbounded_below {
+    use super::*;
+
+    pub fn serialize<T: Float + Serialize, S: Serializer>(
+        x: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        finite_or_none(*x, T::neg_infinity()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: Float + Deserialize<'de>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        Ok(Option::deserialize(deserializer)?.unwrap_or_else(T::neg_infinity))
+    }
+}
+
+/// optional (lo, hi) bounds, each written like a clamp bound
+pub(crate) mod limits {
+    use super::*;
+
+    pub fn serialize<T: Float + Serialize, S: Serializer>(
+        limits: &Option<(T, T)>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        limits
+            .map(|(lo, hi)| {
+                (
+                    finite_or_none(lo, T::neg_infinity()),
+                    finite_or_none(hi, T::infinity()),
+                )
+            })
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: Float + Deserialize<'de>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<(T, T)>, D::Error> {
+        let limits: Option<(Option<T>, Option<T>)> = Option::deserialize(deserializer)?;
+        Ok(limits.map(|(lo, hi)| {
+            (
+                lo.unwrap_or_else(T::neg_infinity),
+                hi.unwrap_or_else(T::infinity),
+            )
+        }))
+    }
+}
+
+fn finite_or_none<T: Float>(x: T, unbounded: T) -> Option<T> {
+    if x == unbounded {
+        None
+    } else {
+        Some(x)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{DerivativeMode, PidController};
+
+    use std::string::ToString;
+
+    fn round_trip(pid: &PidController<f64>) -> PidController<f64> {
+        let json = serde_json::to_string(pid).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn default_controller_round_trips() {
+        let pid = PidController::<f64>::new(0.1, (-1.0, 1.0));
+        assert_eq!(round_trip(&pid), pid);
+    }
+
+    #[test]
+    fn checkpoint_resumes_where_it_left_off() {
+        let mut pid = PidController::<f64>::new(0.1, (-2.0, 2.0));
+        pid.set_kp(1.0).unwrap();
+        pid.set_ki(0.5).unwrap();
+        pid.set_kd(0.2).unwrap();
+        pid.set_smooth(0.4).unwrap();
+        pid.set_derivative_mode(DerivativeMode::OnMeasurement);
+        pid.set_setpoint_ramp_rates(1.0, 2.0).unwrap();
+        pid.set_setpoint(1.5).unwrap();
+        let mut y = 0.0;
+        for _ in 0..30 {
+            let u = pid.update(y);
+            y += 0.1 * (u - y);
+        }
+
+        let mut restored = round_trip(&pid);
+        assert_eq!(restored, pid);
+        for _ in 0..30 {
+            let u = pid.update(y);
+            assert_eq!(restored.update(y), u);
+            y += 0.1 * (u - y);
+        }
+    }
+
+    #[test]
+    fn unbounded_controller_resumes_where_it_left_off() {
+        let pid = PidController::<f64>::default();
+        assert_eq!(round_trip(&pid), pid);
+
+        let mut pid = PidController::<f64>::new(0.1, (f64::NEG_INFINITY, 3.0));
+        pid.set_gains(1.0, 0.5, 0.2).unwrap();
+        pid.set_integral_limits(f64::NEG_INFINITY, 2.0).unwrap();
+        pid.set_derivative_filter(f64::INFINITY).unwrap();
+        for k in 0..20 {
+            pid.step(1.0 - 0.1 * k as f64);
+        }
+
+        let mut restored = round_trip(&pid);
+        assert_eq!(restored, pid);
+        assert_eq!(restored.clamp_lo(), f64::NEG_INFINITY);
+        assert_eq!(restored.clamp_hi(), 3.0);
+        assert_eq!(restored.integral_limits(), Some((f64::NEG_INFINITY, 2.0)));
+        assert_eq!(restored.derivative_filter(), None);
+        for k in 0..20 {
+            let e = (k as f64).sin();
+            assert_eq!(restored.step(e), pid.step(e));
+        }
+    }
+
+    #[test]
+    fn disabled_settings_are_written_as_null() {
+        let mut pid = PidController::<f32>::new(0.1, (-1.0, 1.0));
+        pid.set_kp(1.0).unwrap();
+        let json = serde_json::to_value(pid).unwrap();
+        assert!(json["max_rate"].is_null());
+        assert!(json["integral_threshold"].is_null());
+        assert!(json["setpoint_ramp_up"].is_null());
+        let mut restored: PidController<f32> = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.output_rate_limit(), f32::INFINITY);
+        assert_eq!(restored.integral_separation_threshold(), f32::INFINITY);
+        assert_eq!(
+            restored.setpoint_ramp_rates(),
+            (f32::INFINITY, f32::INFINITY)
+        );
+        assert_eq!(restored.step(5.0), pid.step(5.0));
+
+        pid.set_output_rate_limit(2.0).unwrap();
+        let json = serde_json::to_value(pid).unwrap();
+        assert_eq!(json["max_rate"], 2.0);
+    }
+
+    #[test]
+    fn rejects_settings_the_setters_reject() {
+        let pid = PidController::<f64>::new(0.1, (-1.0, 1.0));
+        let json = serde_json::to_value(pid).unwrap();
+
+        let mut smooth = json.clone();
+        smooth["smooth"] = 1.5.into();
+        let err = serde_json::from_value::<PidController<f64>>(smooth).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "smoothing coefficient must be within [0,1]"
+        );
+
+        let mut clamp = json.clone();
+        clamp["clamp_lo"] = 2.0.into();
+        let err = serde_json::from_value::<PidController<f64>>(clamp).unwrap_err();
+        assert_eq!(err.to_string(), "lower clamp must be below upper clamp");
+
+        let mut dt = json.clone();
+        dt["dt"] = 0.0.into();
+        let err = serde_json::from_value::<PidController<f64>>(dt).unwrap_err();
+        assert_eq!(err.to_string(), "time step must be positive");
+
+        let mut gain = json;
+        gain["kp"] = (-1.0).into();
+        assert!(serde_json::from_value::<PidController<f64>>(gain).is_err());
+
+        assert!(serde_json::from_str::<PidController<f64>>("{}").is_err());
+    }
+}
@@ -0,0 +1,233 @@
+//! closed-loop simulation against a plant closure, for comparing tunings
+//! and for regression tests of a loop.
+
+use crate::PidController;
+
+use alloc::vec::Vec;
+use num_traits::Float;
+
+/// trajectory of a closed-loop simulation, see simulate()
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimResult<T = f64> {
+    /// time of each sample, k * dt
+    pub time: Vec<T>,
+    pub setpoint: Vec<T>,
+
+    /// measurement the controller saw at each sample
+    pub measurement: Vec<T>,
+
+    /// controller output at each sample
+    pub output: Vec<T>,
+
+    /// the run stopped early because the measurement became non-finite or
+    /// moved away from the setpoint by over DIVERGENCE times the setpoint
+    /// change
+    pub diverged: bool,
+
+    dt: T,
+}
+
+/// error beyond this many times the setpoint change counts as divergence
+pub const DIVERGENCE: f64 = 1e6;
+
+/// runs pid against a plant for the given duration, one step per pid.dt().
+///
+/// y: measurement at time 0
+/// setpoint: setpoint as a function of time
+/// plant: applies the output for one dt, returns the new measurement
+///
+/// ```
+/// use pid::{sim, PidController};
+///
+/// let dt = 0.01;
+/// let mut pid = PidController::<f64>::new(dt, (-10.0, 10.0));
+/// pid.set_gains(2.0, 1.0, 0.0).unwrap();
+///
+/// let mut y = 0.0;
+/// let result = sim::simulate(&mut pid, 0.0, 20.0, |_| 1.0, |u| {
+///     y += dt * (u - y);
+///     y
+/// });
+/// assert!(!result.diverged);
+/// assert!(result.steady_state_error().abs() < 1e-3);
+/// assert!(result.settling_time(0.02).unwrap() < 10.0);
+/// ```
+pub fn simulate<T, S, P>(
+    pid: &mut PidController<T>,
+    y: T,
+    duration: T,
+    setpoint: S,
+    mut plant: P,
+) -> SimResult<T>
+where
+    T: Float,
+    S: Fn(T) -> T,
+    P: FnMut(T) -> T,
+{
+    let dt = pid.dt();
+    let steps = (duration / dt).ceil().to_usize().unwrap_or(0);
+    let mut result = SimResult {
+        time: Vec::new(),
+        setpoint: Vec::new(),
+        measurement: Vec::new(),
+        output: Vec::new(),
+        diverged: false,
+        dt,
+    };
+
+    let y0 = y;
+    let mut y = y;
+    let mut span = T::zero();
+    for k in 0..steps {
+        let t = T::from(k).unwrap() * dt;
+        let r = setpoint(t);
+        span = span.max((r - y0).abs());
+        if !y.is_finite() || (r - y).abs() > T::from(DIVERGENCE).unwrap() * span.max(T::one()) {
+            result.diverged = true;
+            break;
+        }
+
+        let u = pid.step_with_setpoint(r, y);
+        result.time.push(t);
+        result.setpoint.push(r);
+        result.measurement.push(y);
+        result.output.push(u);
+        y = plant(u);
+    }
+    result
+}
+
+impl<T: Float> SimResult<T> {
+    /// the metrics below measure the response against the last setpoint,
+    /// starting from the first measurement
+    fn step(&self) -> Option<(T, T)> {
+        Some((*self.measurement.first()?, *self.setpoint.last()?))
+    }
+
+    /// peak beyond the final setpoint, in percent of the setpoint change.
+    /// 0 without a setpoint change.
+    pub fn overshoot(&self) -> T {
+        let (y0, r) = match self.step() {
+            Some(step) => step,
+            None => return T::zero(),
+        };
+        let span = r - y0;
+        if span == T::zero() {
+            return T::zero();
+        }
+        let peak = self
+            .measurement
+            .iter()
+            .map(|&y| (y - r) / span)
+            .fold(T::zero(), T::max);
+        peak * T::from(100).unwrap()
+    }
+
+    /// time from 10% to 90% of the setpoint change, None if 90% is never
+    /// reached or the setpoint does not change
+    pub fn rise_time(&self) -> Option<T> {
+        let (y0, r) = self.step()?;
+        let span = r - y0;
+        if span == T::zero() {
+            return None;
+        }
+        let reached = |fraction: f64| {
+            let fraction = T::from(fraction).unwrap();
+            self.measurement
+                .iter()
+                .position(|&y| (y - y0) / span >= fraction)
+        };
+        let start = reached(0.1)?;
+        let end = reached(0.9)?;
+        Some(self.time[end] - self.time[start])
+    }
+
+    /// time after which the measurement stays within band times the
+    /// setpoint change around the final setpoint, None if it is outside at
+    /// the end of the run
+    pub fn settling_time(&self, band: T) -> Option<T> {
+        let (y0, r) = self.step()?;
+        let tolerance = band * (r - y0).abs();
+        match self
+            .measurement
+            .iter()
+            .rposition(|&y| (y - r).abs() > tolerance)
+        {
+            None => Some(T::zero()),
+            Some(last) if last + 1 < self.time.len() => Some(self.time[last + 1]),
+            Some(_) => None,
+        }
+    }
+
+    /// setpoint minus measurement at the last sample
+    pub fn steady_state_error(&self) -> T {
+        match (self.setpoint.last(), self.measurement.last()) {
+            (Some(&r), Some(&y)) => r - y,
+            _ => T::zero(),
+        }
+    }
+
+    /// integral of |setpoint - measurement| over the run
+    pub fn iae(&self) -> T {
+        self.setpoint
+            .iter()
+            .zip(&self.measurement)
+            .fold(T::zero(), |sum, (&r, &y)| sum + (r - y).abs() * self.dt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first_order(kp: f64) -> SimResult<f64> {
+        let dt = 0.01;
+        let mut pid = PidController::new(dt, (-100.0, 100.0));
+        pid.set_gains(kp, 2.0, 0.0).unwrap();
+        let mut y = 0.0;
+        simulate(
+            &mut pid,
+            0.0,
+            20.0,
+            |_| 1.0,
+            |u| {
+                y += dt * (u - y);
+                y
+            },
+        )
+    }
+
+    #[test]
+    fn metrics_compare_tunings() {
+        let soft = first_order(0.5);
+        let firm = first_order(4.0);
+        assert!(!soft.diverged && !firm.diverged);
+        assert_eq!(soft.time.len(), 2000);
+        assert!(soft.overshoot() > firm.overshoot());
+        assert!(firm.iae() < soft.iae());
+        assert!(firm.rise_time().unwrap() < soft.rise_time().unwrap());
+        assert!(firm.settling_time(0.02).unwrap() < soft.settling_time(0.02).unwrap());
+        assert!(firm.steady_state_error().abs() < 1e-3);
+    }
+
+    #[test]
+    fn stops_on_divergence() {
+        let dt = 0.01;
+        // positive feedback: the plant runs away from the setpoint
+        let mut pid = PidController::new(dt, (-1e9, 1e9));
+        pid.set_kp(1.0).unwrap();
+        let mut y = 0.0;
+        let result = simulate(
+            &mut pid,
+            0.0,
+            1e4,
+            |_| 1.0,
+            |u| {
+                y += dt * (10.0 * y - u);
+                y
+            },
+        );
+        assert!(result.diverged);
+        assert!(result.time.len() < 1_000_000);
+    }
+}
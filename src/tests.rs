@@ -0,0 +1,1457 @@
+use super::*;
+
+use std::vec::Vec;
+
+fn pid(dt: f64, clamp: (f64, f64)) -> PidController {
+    PidController::new(dt, clamp)
+}
+
+fn assert_close(a: f64, b: f64, tol: f64) {
+    assert!(
+        (a - b).abs() <= tol,
+        "{} differs from {} by more than {}",
+        a,
+        b,
+        tol
+    );
+}
+
+/// deterministic noise in [-1, 1)
+struct Noise(u64);
+
+impl Noise {
+    fn next(&mut self) -> f64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+}
+
+/// first-order plant y' = (u - y) / tau, one explicit Euler step
+fn lag(y: f64, u: f64, tau: f64, dt: f64) -> f64 {
+    y + dt * (u - y) / tau
+}
+
+#[test]
+fn output_stays_within_clamp() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_kp(10.0).unwrap();
+    pid.set_ki(5.0).unwrap();
+    pid.set_kd(1.0).unwrap();
+    let mut i_after_first = None;
+    for _ in 0..100 {
+        let u = pid.step(1000.0);
+        assert_eq!(u, 10.0);
+        assert!(!pid.unclamped());
+        assert!(pid.raw_output() > 10.0);
+        let i = *i_after_first.get_or_insert(pid.i());
+        assert_eq!(pid.i(), i);
+    }
+    for _ in 0..100 {
+        assert_eq!(pid.step(-1000.0), -10.0);
+    }
+}
+
+#[test]
+fn output_exactly_at_bounds() {
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    pid.set_kp(1.0).unwrap();
+
+    assert_eq!(pid.step(1.0), 1.0);
+    assert_eq!(pid.saturation(), Saturation::Upper);
+    assert!(!pid.unclamped());
+
+    assert_eq!(pid.step(-1.0), -1.0);
+    assert_eq!(pid.saturation(), Saturation::Lower);
+    assert!(!pid.unclamped());
+
+    assert_eq!(pid.step(0.5), 0.5);
+    assert_eq!(pid.saturation(), Saturation::None);
+    assert!(pid.unclamped());
+
+    assert_eq!(pid.step(5.0), 1.0);
+    assert_eq!(pid.raw_output(), 5.0);
+}
+
+#[test]
+fn nan_clamp_is_rejected() {
+    assert_eq!(
+        PidController::try_new(0.1, (f64::NAN, 1.0)),
+        Err(PidError::NaN)
+    );
+    assert_eq!(
+        PidController::try_new(0.1, (-1.0, f64::NAN)),
+        Err(PidError::NaN)
+    );
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    assert_eq!(pid.set_clamp((f64::NAN, 1.0)), Err(PidError::NaN));
+    assert_eq!((pid.clamp_lo(), pid.clamp_hi()), (-1.0, 1.0));
+}
+
+#[test]
+fn try_new_rejects_what_new_panics_on() {
+    assert_eq!(
+        PidController::try_new(0.0, (-1.0, 1.0)),
+        Err(PidError::NonPositiveDt)
+    );
+    assert_eq!(
+        PidController::try_new(0.1, (1.0, 1.0)),
+        Err(PidError::InvertedClamp)
+    );
+    assert!(PidController::try_new(0.1, (-1.0, 1.0)).is_ok());
+}
+
+#[test]
+#[should_panic(expected = "invalid PidController: time step must be positive")]
+fn new_panics_on_a_non_positive_dt() {
+    PidController::new(-0.1, (-1.0, 1.0));
+}
+
+#[test]
+fn gain_getters_read_back_the_setters() {
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    pid.set_kp(1.0).unwrap();
+    pid.set_ki(2.0).unwrap();
+    pid.set_kd(3.0).unwrap();
+    assert_eq!((pid.kp(), pid.ki(), pid.kd()), (1.0, 2.0, 3.0));
+}
+
+#[test]
+fn p_is_the_proportional_contribution() {
+    let mut pid = pid(0.1, (-100.0, 100.0));
+    pid.set_kp(3.0).unwrap();
+    pid.step(2.0);
+    assert_eq!(pid.p(), 6.0);
+}
+
+#[test]
+fn contributions_add_up_to_the_output() {
+    let mut pid = pid(0.1, (-100.0, 100.0));
+    pid.set_kp(1.5).unwrap();
+    pid.set_ki(0.8).unwrap();
+    pid.set_kd(0.2).unwrap();
+    for k in 0..50 {
+        let e = (k as f64 * 0.3).sin();
+        let u = pid.step(e);
+        assert_eq!(pid.p() + pid.i_contribution() + pid.d_contribution(), u);
+        assert_eq!(pid.d_contribution(), pid.kd() * pid.d());
+    }
+}
+
+#[test]
+fn terms_add_up_to_the_output() {
+    let mut pid = pid(0.1, (-100.0, 100.0));
+    pid.set_kp(1.5).unwrap();
+    pid.set_ki(0.8).unwrap();
+    pid.set_kd(0.2).unwrap();
+    pid.set_smooth(0.5).unwrap();
+    for k in 0..50 {
+        let e = (k as f64 * 0.3).sin();
+        let out = pid.step_detailed(e);
+        assert!(!out.saturated);
+        assert_close(out.p + out.i + out.d, out.total, 1e-12);
+        assert_eq!(out.p, pid.p());
+        assert_eq!(out.i, pid.i_contribution());
+        assert_eq!(out.d, pid.d_contribution());
+    }
+}
+
+#[test]
+fn f32_matches_hand_computed_steps() {
+    let mut pid = PidController::<f32>::new(0.5, (-10.0, 10.0));
+    pid.set_kp(2.0).unwrap();
+    pid.set_ki(1.0).unwrap();
+    pid.set_kd(0.5).unwrap();
+    // p = 2, ki * i = 0.5, kd * d = 0.5 * (1 - 0) / 0.5
+    assert_eq!(pid.step(1.0), 3.5);
+    // p = 1, ki * i = 0.75, kd * d = 0.5 * (0.5 - 1) / 0.5
+    assert_eq!(pid.step(0.5), 1.25);
+    // p = 0, ki * i = 0.75, kd * d = 0.5 * (0 - 0.5) / 0.5
+    assert_eq!(pid.step(0.0), 0.25);
+}
+
+#[test]
+fn f32_and_f64_agree() {
+    let mut single = PidController::<f32>::new(0.01, (-5.0, 5.0));
+    let mut double = PidController::new(0.01, (-5.0, 5.0));
+    single.set_kp(1.2).unwrap();
+    single.set_ki(0.4).unwrap();
+    single.set_kd(0.05).unwrap();
+    single.set_smooth(0.3).unwrap();
+    double.set_kp(1.2).unwrap();
+    double.set_ki(0.4).unwrap();
+    double.set_kd(0.05).unwrap();
+    double.set_smooth(0.3).unwrap();
+    for k in 0..1000 {
+        let e = 2.0 * (k as f64 * 0.01).sin();
+        let u32 = single.step(e as f32);
+        let u64 = double.step(e);
+        assert_close(u32 as f64, u64, 1e-4 * (1.0 + u64.abs()));
+    }
+}
+
+#[test]
+fn derivative_on_measurement_has_no_kick() {
+    let mut on_error = pid(0.1, (-1000.0, 1000.0));
+    on_error.set_kd(1.0).unwrap();
+    let mut on_measurement = pid(0.1, (-1000.0, 1000.0));
+    on_measurement.set_kd(1.0).unwrap();
+    on_measurement.set_derivative_mode(DerivativeMode::OnMeasurement);
+
+    on_error.step_with_setpoint(0.0, 0.0);
+    on_measurement.step_with_setpoint(0.0, 0.0);
+    assert_eq!(on_error.step_with_setpoint(10.0, 0.0), 100.0);
+    assert_eq!(on_measurement.step_with_setpoint(10.0, 0.0), 0.0);
+    assert_eq!(on_measurement.step_with_setpoint(10.0, 0.5), -5.0);
+}
+
+#[test]
+fn first_measurement_does_not_kick() {
+    let mut pid = pid(0.1, (-1000.0, 1000.0));
+    pid.set_kd(1.0).unwrap();
+    pid.set_derivative_mode(DerivativeMode::OnMeasurement);
+    for _ in 0..2 {
+        for _ in 0..5 {
+            assert_eq!(pid.step_with_setpoint(50.0, 50.0), 0.0);
+        }
+        assert_eq!(pid.step_with_setpoint(50.0, 50.5), -5.0);
+        pid.reset();
+    }
+}
+
+#[test]
+fn invalid_gains_keep_the_previous_ones() {
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    pid.set_gains(1.0, 2.0, 3.0).unwrap();
+    assert_eq!(pid.set_kp(-1.0), Err(PidError::NegativeGain));
+    assert_eq!(pid.set_ki(f64::NAN), Err(PidError::NaN));
+    assert_eq!(pid.set_kd(-6.0), Err(PidError::NegativeGain));
+    assert_eq!(pid.set_gains(4.0, 5.0, -6.0), Err(PidError::NegativeGain));
+    assert_eq!((pid.kp(), pid.ki(), pid.kd()), (1.0, 2.0, 3.0));
+
+    pid.set_smooth(0.5).unwrap();
+    assert_eq!(pid.set_smooth(1.5), Err(PidError::SmoothOutOfRange));
+    assert_eq!(pid.set_smooth(f64::NAN), Err(PidError::NaN));
+    assert_eq!(pid.smooth(), 0.5);
+}
+
+#[test]
+fn reset_matches_a_fresh_controller() {
+    let configure = |pid: &mut PidController<f64>| {
+        pid.set_kp(1.0).unwrap();
+        pid.set_ki(0.5).unwrap();
+        pid.set_kd(0.1).unwrap();
+        pid.set_smooth(0.4).unwrap();
+        pid.set_derivative_mode(DerivativeMode::OnMeasurement);
+        pid.set_input_filter(0.7).unwrap();
+    };
+    let mut fresh = pid(0.1, (-2.0, 2.0));
+    configure(&mut fresh);
+    let mut used = pid(0.1, (-2.0, 2.0));
+    configure(&mut used);
+
+    for k in 0..40 {
+        used.step_with_setpoint(3.0, k as f64 * 0.1);
+    }
+    used.reset();
+    assert_eq!((used.i(), used.d(), used.e_prev()), (0.0, 0.0, 0.0));
+    assert!(used.unclamped());
+    for k in 0..40 {
+        let y = (k as f64).cos();
+        assert_eq!(
+            used.step_with_setpoint(1.0, y),
+            fresh.step_with_setpoint(1.0, y)
+        );
+    }
+}
+
+#[test]
+fn reverse_acting_plant_converges() {
+    // cooling loop y' = 2 - u: more output lowers the measurement
+    let dt = 0.01;
+    let mut pid = pid(dt, (0.0, 10.0));
+    pid.set_kp(2.0).unwrap();
+    pid.set_ki(1.0).unwrap();
+    pid.set_direction(ControllerDirection::Reverse);
+    let mut y = 5.0;
+    for _ in 0..5000 {
+        let u = pid.step_with_setpoint(1.0, y);
+        y += dt * (2.0 - u);
+    }
+    assert_close(y, 1.0, 1e-3);
+    assert_close(pid.step_with_setpoint(1.0, y), 2.0, 1e-2);
+}
+
+#[test]
+fn restore_rolls_back_to_a_snapshot() {
+    let mut pid = pid(0.1, (-5.0, 5.0));
+    pid.set_kp(1.0).unwrap();
+    pid.set_ki(0.5).unwrap();
+    pid.set_kd(0.2).unwrap();
+    pid.set_derivative_mode(DerivativeMode::OnMeasurement);
+    for k in 0..10 {
+        pid.step_with_setpoint(1.0, k as f64 * 0.05);
+    }
+    let snapshot = pid.state();
+    let outputs: [f64; 5] = core::array::from_fn(|k| pid.step_with_setpoint(1.0, k as f64 * 0.1));
+
+    pid.restore(&snapshot);
+    assert_eq!(pid.state(), snapshot);
+    for (k, &u) in outputs.iter().enumerate() {
+        assert_eq!(pid.step_with_setpoint(1.0, k as f64 * 0.1), u);
+    }
+}
+
+#[test]
+fn jittered_dt_integrates_elapsed_time() {
+    let mut pid = pid(0.01, (-1e9, 1e9));
+    pid.set_ki(1.0).unwrap();
+    let mut noise = Noise(7);
+    let mut elapsed = 0.0;
+    for _ in 0..1000 {
+        let dt = 0.01 + 0.005 * noise.next();
+        elapsed += dt;
+        pid.step_with_dt(2.0, dt);
+    }
+    assert_close(pid.i(), 2.0 * elapsed, 1e-9);
+
+    let mut pid = self::pid(0.01, (-1e9, 1e9));
+    pid.set_ki(1.0).unwrap();
+    for ms in [5, 15, 10, 12, 8] {
+        pid.step_elapsed(2.0, Duration::from_millis(ms));
+    }
+    assert_close(pid.i(), 2.0 * 0.05, 1e-12);
+}
+
+#[test]
+fn irregular_intervals_match_the_analytic_integral() {
+    // e(t) = t, integral t^2 / 2. the loop runs slower than the configured
+    // dt, so integrating over a fixed dt drifts away from the reference.
+    let mut pid = pid(0.01, (-1e9, 1e9));
+    pid.set_ki(1.0).unwrap();
+    let mut fixed = self::pid(0.01, (-1e9, 1e9));
+    fixed.set_ki(1.0).unwrap();
+    let mut noise = Noise(2);
+    let mut t = 0.0;
+    for _ in 0..500 {
+        let dt = 0.01 * (1.3 + 0.5 * noise.next());
+        t += dt;
+        pid.step_with_dt(t, dt);
+        fixed.step(t);
+    }
+    let reference = t * t / 2.0;
+    assert_close(pid.i(), reference, 0.005 * reference);
+    assert!((fixed.i() - reference).abs() > 0.05 * reference);
+}
+
+#[test]
+fn non_positive_dt_skips_integration() {
+    let mut pid = pid(0.1, (-100.0, 100.0));
+    pid.set_kp(1.0).unwrap();
+    pid.set_ki(1.0).unwrap();
+    pid.set_kd(1.0).unwrap();
+    pid.step(1.0);
+    let state = pid.state();
+    assert_eq!(pid.step_with_dt(2.0, 0.0), 2.0 + state.i + state.d);
+    assert_eq!(pid.i(), state.i);
+    assert_eq!(pid.d(), state.d);
+    assert!(pid.step_with_dt(2.0, -1.0).is_finite());
+}
+
+#[test]
+fn reset_integral_keeps_derivative_history() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_kp(1.0).unwrap();
+    pid.set_ki(1.0).unwrap();
+    pid.set_kd(1.0).unwrap();
+    pid.set_smooth(0.5).unwrap();
+    pid.step(1.0);
+    pid.step(2.0);
+    let d = pid.d();
+    pid.reset_integral();
+    assert_eq!(pid.i(), 0.0);
+    assert_eq!(pid.d(), d);
+    assert_eq!(pid.e_prev(), 2.0);
+}
+
+#[test]
+fn setpoint_and_measurement_match_the_error() {
+    let mut by_measurement = pid(0.1, (-100.0, 100.0));
+    let mut by_error = pid(0.1, (-100.0, 100.0));
+    for pid in [&mut by_measurement, &mut by_error] {
+        pid.set_kp(1.0).unwrap();
+        pid.set_ki(0.5).unwrap();
+        pid.set_kd(0.2).unwrap();
+    }
+    assert_eq!(
+        by_measurement.step_with_setpoint(10.0, 7.0),
+        by_error.step(3.0)
+    );
+    assert_eq!(
+        by_measurement.step_with_setpoint(10.0, 8.0),
+        by_error.step(2.0)
+    );
+}
+
+#[test]
+fn feedforward_holds_output_at_zero_error() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_kp(1.0).unwrap();
+    pid.set_ki(1.0).unwrap();
+    pid.set_kd(1.0).unwrap();
+    for _ in 0..10 {
+        assert_eq!(pid.step_ff(0.0, 2.5), 2.5);
+    }
+}
+
+#[test]
+fn saturating_feedforward_does_not_wind_up() {
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    pid.set_ki(1.0).unwrap();
+    assert_eq!(pid.step_ff(0.5, 5.0), 1.0);
+    let i = pid.i();
+    for _ in 0..100 {
+        assert_eq!(pid.step_ff(0.5, 5.0), 1.0);
+    }
+    assert_eq!(pid.i(), i);
+}
+
+/// runs a PI loop on a first-order plant through a load disturbance that
+/// ramps up until the output saturates, returns the overshoot after the
+/// disturbance goes away and the number of steps until the measurement
+/// stays within 0.02 of the setpoint
+fn recovery_after_saturation(anti_windup: AntiWindup) -> (f64, usize) {
+    let dt = 0.01;
+    let mut pid = pid(dt, (-1.0, 1.0));
+    pid.set_kp(2.0).unwrap();
+    pid.set_ki(2.0).unwrap();
+    pid.set_anti_windup(anti_windup).unwrap();
+    let mut y = 0.0;
+    let mut peak = f64::NEG_INFINITY;
+    let mut settled = 0;
+    for k in 0..6000 {
+        let disturbance = if k < 1000 {
+            -1.5 * (k as f64 / 500.0).min(1.0)
+        } else {
+            0.0
+        };
+        let u = pid.step_with_setpoint(0.5, y);
+        y = lag(y, u + disturbance, 1.0, dt);
+        if k >= 1000 {
+            peak = peak.max(y);
+            if (y - 0.5).abs() > 0.02 {
+                settled = k - 1000;
+            }
+        }
+    }
+    (peak - 0.5, settled)
+}
+
+#[test]
+fn back_calculation_recovers_faster_than_freezing() {
+    let back_calculation = recovery_after_saturation(AntiWindup::BackCalculation { kaw: 1.0 });
+    for freeze in [AntiWindup::Clamp, AntiWindup::ConditionalIntegration] {
+        let frozen = recovery_after_saturation(freeze);
+        assert!(
+            back_calculation.0 < frozen.0 / 2.0,
+            "{:?} vs {:?}",
+            back_calculation,
+            frozen
+        );
+        assert!(
+            back_calculation.1 < frozen.1,
+            "{:?} vs {:?}",
+            back_calculation,
+            frozen
+        );
+    }
+}
+
+#[test]
+fn infinite_gains_are_rejected() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_kd(1.0).unwrap();
+    assert_eq!(pid.set_kaw(f64::INFINITY), Err(PidError::InfiniteGain));
+    assert_eq!(
+        pid.set_anti_windup(AntiWindup::BackCalculation { kaw: f64::INFINITY }),
+        Err(PidError::InfiniteGain)
+    );
+    assert_eq!(pid.anti_windup(), AntiWindup::ConditionalIntegration);
+    assert_eq!(pid.set_kp(f64::INFINITY), Err(PidError::InfiniteGain));
+    assert_eq!(pid.set_ki(f64::INFINITY), Err(PidError::InfiniteGain));
+    assert_eq!(pid.set_kd(f64::INFINITY), Err(PidError::InfiniteGain));
+    assert_eq!(pid.kd(), 1.0);
+
+    pid.set_ki(1.0).unwrap();
+    pid.set_kaw(0.5).unwrap();
+    for _ in 0..20 {
+        pid.step(50.0);
+        assert!(pid.i().is_finite());
+    }
+}
+
+#[test]
+fn rate_limit_stays_within_a_clamp_excluding_zero() {
+    let mut pid = pid(0.1, (1.0, 2.0));
+    pid.set_kp(1.0).unwrap();
+    pid.set_output_rate_limit(1.0).unwrap();
+    // the rate limit starts from 0, the clamp then lifts the output into range
+    assert_eq!(pid.step(1.5), 1.0);
+    assert_close(pid.step(1.5), 1.1, 1e-12);
+    assert_close(pid.step(1.5), 1.2, 1e-12);
+    for _ in 0..10 {
+        let u = pid.step(1.5);
+        assert!((1.0..=2.0).contains(&u), "{}", u);
+    }
+    assert_eq!(pid.step(1.5), 1.5);
+}
+
+#[test]
+fn rate_limit_ramps_the_output() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_kp(100.0).unwrap();
+    pid.set_output_rate_limit(2.0).unwrap();
+    let mut last = 0.0;
+    for k in 1..=50 {
+        let u = pid.step(1.0);
+        assert_close(u - last, 0.2, 1e-12);
+        assert_close(u, 0.2 * k as f64, 1e-9);
+        assert_eq!(pid.saturation(), Saturation::Upper);
+        last = u;
+    }
+    assert_eq!(pid.step(1.0), 10.0);
+    assert_eq!(
+        pid.set_output_rate_limit(0.0),
+        Err(PidError::NonPositiveRateLimit)
+    );
+}
+
+#[test]
+fn incremental_sum_matches_positional() {
+    let mut positional = pid(0.1, (-1e9, 1e9));
+    let mut incremental = pid(0.1, (-1e9, 1e9));
+    for pid in [&mut positional, &mut incremental] {
+        pid.set_kp(1.3).unwrap();
+        pid.set_ki(0.7).unwrap();
+        pid.set_kd(0.2).unwrap();
+    }
+    let mut sum = 0.0;
+    for k in 0..200 {
+        let e = (k as f64 * 0.1).sin() + 0.3;
+        sum += incremental.step_incremental(e);
+        assert_close(sum, positional.step(e), 1e-9);
+    }
+}
+
+#[test]
+fn conditional_integration_resumes_on_sign_reversal() {
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    pid.set_kp(10.0).unwrap();
+    pid.set_ki(1.0).unwrap();
+    pid.step(1.0);
+    assert_eq!(pid.saturation(), Saturation::Upper);
+    let i = pid.i();
+    pid.step(1.0);
+    assert_eq!(pid.i(), i);
+    pid.step(-0.01);
+    assert_close(pid.i(), i - 0.001, 1e-15);
+
+    let mut clamp = self::pid(0.1, (-1.0, 1.0));
+    clamp.set_kp(10.0).unwrap();
+    clamp.set_ki(1.0).unwrap();
+    clamp.set_anti_windup(AntiWindup::Clamp).unwrap();
+    clamp.step(1.0);
+    let i = clamp.i();
+    clamp.step(-0.01);
+    assert_eq!(clamp.i(), i);
+}
+
+#[test]
+fn rate_limit_informs_anti_windup() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_kp(1.0).unwrap();
+    pid.set_ki(1.0).unwrap();
+    pid.set_output_rate_limit(0.5).unwrap();
+    pid.step(5.0);
+    let i = pid.i();
+    for _ in 0..10 {
+        pid.step(5.0);
+    }
+    assert_eq!(pid.i(), i);
+}
+
+#[test]
+fn zero_setpoint_weight_has_no_proportional_spike() {
+    let mut pid = pid(0.1, (-100.0, 100.0));
+    pid.set_kp(2.0).unwrap();
+    pid.set_b(0.0).unwrap();
+    assert_eq!(pid.step_with_setpoint(0.0, 0.0), 0.0);
+    assert_eq!(pid.step_with_setpoint(5.0, 0.0), 0.0);
+    assert_eq!(pid.p(), 0.0);
+    // P still acts on the measurement
+    assert_eq!(pid.step_with_setpoint(5.0, 1.0), -2.0);
+    assert_eq!(pid.set_b(1.5), Err(PidError::WeightOutOfRange));
+}
+
+#[test]
+fn tracking_constant_is_the_time_constant_of_the_integral_contribution() {
+    let dt = 0.01;
+    let mut pid = pid(dt, (-1.0, 1.0));
+    pid.set_ki(2.0).unwrap();
+    pid.set_anti_windup_tracking_constant(0.5).unwrap();
+    assert_eq!(pid.anti_windup(), AntiWindup::BackCalculation { kaw: 1.0 });
+
+    // ki * i = 3 against an upper clamp of 1: the excess of 2 decays with
+    // time constant 0.5
+    let mut state = pid.state();
+    state.i = 1.5;
+    pid.restore(&state);
+    for k in 1..=50 {
+        pid.step(0.0);
+        assert_close(
+            pid.i_contribution(),
+            1.0 + 2.0 * (1.0 - dt / 0.5).powi(k),
+            1e-12,
+        );
+    }
+    assert_close(pid.i_contribution() - 1.0, 2.0 * (-1.0f64).exp(), 0.02);
+
+    pid.set_ki(0.0).unwrap();
+    assert_eq!(
+        pid.set_anti_windup_tracking_constant(0.5),
+        Err(PidError::InfiniteGain)
+    );
+    assert_eq!(
+        pid.set_anti_windup_tracking_constant(0.0),
+        Err(PidError::NonPositiveTimeConstant)
+    );
+}
+
+#[test]
+fn bias_offsets_the_output() {
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    pid.set_output_bias(0.3).unwrap();
+    assert_eq!(pid.step(0.0), 0.3);
+    assert_eq!(pid.saturation(), Saturation::None);
+
+    pid.set_output_bias(5.0).unwrap();
+    assert_eq!(pid.step(0.0), 1.0);
+    assert_eq!(pid.saturation(), Saturation::Upper);
+    assert_eq!(pid.set_output_bias(f64::NAN), Err(PidError::NaN));
+}
+
+#[test]
+fn integral_separation_reduces_overshoot_on_dead_time_plant() {
+    // first order plus dead time e^-0.5s / (s + 1)
+    let overshoot = |band: f64| {
+        let dt = 0.01;
+        let mut pid = pid(dt, (-100.0, 100.0));
+        pid.set_kp(1.0).unwrap();
+        pid.set_ki(1.0).unwrap();
+        pid.set_integral_separation_threshold(band).unwrap();
+        let mut delay = [0.0; 50];
+        let mut y = 0.0;
+        let mut peak: f64 = 0.0;
+        for k in 0..3000 {
+            let u = pid.step_with_setpoint(10.0, y);
+            let delayed = core::mem::replace(&mut delay[k % 50], u);
+            y = lag(y, delayed, 1.0, dt);
+            peak = peak.max(y);
+        }
+        assert_close(y, 10.0, 0.05);
+        peak - 10.0
+    };
+    let without = overshoot(f64::INFINITY);
+    let with = overshoot(6.0);
+    assert!(with < without / 2.0, "{} vs {}", with, without);
+}
+
+#[test]
+fn bumpless_gain_changes_keep_the_output() {
+    let run = || {
+        let mut pid = pid(0.1, (-10.0, 10.0));
+        pid.set_kp(1.0).unwrap();
+        pid.set_ki(1.0).unwrap();
+        pid.set_kd(0.5).unwrap();
+        for _ in 0..5 {
+            pid.step(1.0);
+        }
+        pid
+    };
+    let reference = run();
+    let expected = run().step(1.0);
+
+    let mut changed = run();
+    changed.set_kp_bumpless(2.0).unwrap();
+    changed.set_ki_bumpless(3.0).unwrap();
+    changed.set_kd_bumpless(0.1).unwrap();
+    let state = changed.state();
+    let u = state.p + changed.ki() * state.i + changed.kd() * state.d;
+    assert_close(u, reference.state().u_raw, 1e-12);
+    assert!((changed.step(1.0) - expected).abs() < 0.5);
+}
+
+#[test]
+fn deadband_holds_the_output_on_noise() {
+    // position loop settled near the setpoint, sensor noise of +-0.02
+    let mut pid = pid(0.01, (-10.0, 10.0));
+    pid.set_kp(2.0).unwrap();
+    pid.set_ki(1.0).unwrap();
+    pid.set_deadband(0.05).unwrap();
+    let mut noise = Noise(11);
+    pid.step_with_setpoint(1.0, 0.9);
+    let held = pid.step_with_setpoint(1.0, 0.99);
+    for _ in 0..1000 {
+        let u = pid.step_with_setpoint(1.0, 1.0 + 0.02 * noise.next());
+        assert_eq!(u, held);
+    }
+}
+
+#[test]
+fn standard_gains_round_trip() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_standard_gains(2.0, 4.0, 0.5).unwrap();
+    assert_eq!(pid.ki(), 0.5);
+    assert_eq!(pid.kd(), 1.0);
+    assert_eq!(pid.ti(), 4.0);
+    assert_eq!(pid.td(), 0.5);
+
+    pid.set_kp(3.0).unwrap();
+    pid.set_ki(1.5).unwrap();
+    pid.set_kd(0.6).unwrap();
+    assert_eq!(pid.ti(), 2.0);
+    assert_close(pid.td(), 0.2, 1e-15);
+
+    for ti in [0.0, f64::INFINITY] {
+        pid.set_standard_gains(2.0, ti, 0.0).unwrap();
+        assert_eq!(pid.ki(), 0.0);
+        assert_eq!(pid.ti(), f64::INFINITY);
+    }
+}
+
+#[test]
+fn standard_gains_change_is_bumpless() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_standard_gains(1.0, 2.0, 0.0).unwrap();
+    for _ in 0..20 {
+        pid.step(1.0);
+    }
+    let before = pid.i_contribution();
+    pid.set_standard_gains(1.0, 0.5, 0.0).unwrap();
+    assert_close(pid.i_contribution(), before, 1e-12);
+}
+
+#[test]
+fn track_output_follows_the_operator() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_kp(2.0).unwrap();
+    pid.set_ki(1.0).unwrap();
+    pid.set_mode(Mode::Manual);
+    for k in 0..10 {
+        pid.track_output(3.0, 0.1 * k as f64);
+    }
+    pid.set_mode(Mode::Auto);
+    assert_close(pid.step(0.9), 3.0 + 0.1 * 0.9, 1e-12);
+}
+
+#[test]
+fn leak_decays_the_integral_at_zero_error() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_ki(1.0).unwrap();
+    pid.set_leak(0.5).unwrap();
+    let mut state = pid.state();
+    state.i = 4.0;
+    pid.restore(&state);
+    for k in 1..=100 {
+        pid.step(0.0);
+        assert_close(pid.i(), 4.0 * (-0.05 * k as f64).exp(), 1e-9);
+    }
+    assert!(pid.i() < 4.0 * 0.01);
+}
+
+#[test]
+fn leak_never_flips_the_integral() {
+    let mut pid = pid(3.0, (-100.0, 100.0));
+    pid.set_ki(1.0).unwrap();
+    pid.set_leak(1.0).unwrap();
+    let mut state = pid.state();
+    state.i = 5.0;
+    pid.restore(&state);
+    let mut last = pid.i();
+    for _ in 0..10 {
+        pid.step(0.0);
+        assert!(pid.i() >= 0.0 && pid.i() < last);
+        last = pid.i();
+    }
+    pid.restore(&state);
+    pid.step_with_dt(0.0, 1e3);
+    assert!(pid.i() >= 0.0 && pid.i() < 1e-12);
+}
+
+#[test]
+fn leak_rejects_invalid_rates() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    assert_eq!(pid.set_leak(2.0), Ok(()));
+    assert_eq!(pid.set_leak(-0.1), Err(PidError::LeakOutOfRange));
+    assert_eq!(pid.set_leak(f64::INFINITY), Err(PidError::LeakOutOfRange));
+    assert_eq!(pid.set_leak(f64::NAN), Err(PidError::NaN));
+    assert_eq!(pid.leak(), 2.0);
+}
+
+#[test]
+fn clones_compare_equal_until_stepped() {
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    pid.set_gains(1.0, 0.5, 0.2).unwrap();
+    for k in 0..10 {
+        pid.step(k as f64 * 0.05);
+    }
+    let mut copy = pid;
+    assert_eq!(copy, pid);
+    assert_eq!(copy.step(0.3), pid.step(0.3));
+    assert_eq!(copy, pid);
+    copy.step(0.2);
+    assert_ne!(copy, pid);
+}
+
+#[test]
+fn derivative_tau_is_independent_of_dt() {
+    let tau = 0.1;
+    let respond = |dt: f64| {
+        let mut pid = pid(dt, (-10.0, 10.0));
+        pid.set_kd(1.0).unwrap();
+        pid.set_derivative_tau(tau).unwrap();
+        assert_close(pid.derivative_tau(), tau, 1e-12);
+        let steps = (1.0 / dt).round() as usize;
+        for k in 0..=steps {
+            pid.step((k as f64 * dt * 3.0).sin());
+        }
+        pid.d()
+    };
+    // derivative of sin(3t) is 3 cos(3t), lagged by the filter
+    let slow = respond(0.01);
+    let fast = respond(0.001);
+    assert_close(slow, fast, 0.05);
+    assert!((fast - 3.0 * 3.0f64.cos()).abs() > 0.05);
+}
+
+#[test]
+fn derivative_tau_and_smooth_override_each_other() {
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    pid.set_derivative_tau(0.3).unwrap();
+    assert_close(pid.smooth(), 0.25, 1e-12);
+    pid.set_smooth(0.5).unwrap();
+    assert_close(pid.derivative_tau(), 0.1, 1e-12);
+}
+
+#[test]
+fn incremental_step_does_not_wind_up() {
+    let mut positional = pid(0.1, (-1.0, 1.0));
+    let mut incremental = pid(0.1, (-1e9, 1e9));
+    positional.set_anti_windup(AntiWindup::None).unwrap();
+    for pid in [&mut positional, &mut incremental] {
+        pid.set_kp(1.0).unwrap();
+        pid.set_ki(1.0).unwrap();
+    }
+    // an actuator that stops at its limits accumulates du
+    let mut actuator = 0.0_f64;
+    for _ in 0..50 {
+        positional.step(5.0);
+        actuator = (actuator + incremental.step_incremental(5.0)).clamp(-1.0, 1.0);
+    }
+    assert_eq!(actuator, 1.0);
+
+    // the positional integrator has wound up to 25 and stays saturated,
+    // the accumulated output leaves the limit at once
+    assert_eq!(positional.step(-0.1), 1.0);
+    actuator = (actuator + incremental.step_incremental(-0.1)).clamp(-1.0, 1.0);
+    assert!(actuator < 1.0);
+}
+
+#[test]
+fn incremental_step_is_rate_limited() {
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    pid.set_kp(10.0).unwrap();
+    pid.set_output_rate_limit(1.0).unwrap();
+    assert_close(pid.step_incremental(5.0), 0.1, 1e-12);
+    assert_close(pid.step_incremental(-5.0), -0.1, 1e-12);
+}
+
+#[test]
+fn default_values() {
+    let mut pid = PidController::<f64>::default();
+    assert_eq!(pid.dt(), 1.0);
+    assert_eq!((pid.kp(), pid.ki(), pid.kd()), (0.0, 0.0, 0.0));
+    assert_eq!(pid.smooth(), 1.0);
+    assert_eq!(pid.clamp_lo(), f64::NEG_INFINITY);
+    assert_eq!(pid.clamp_hi(), f64::INFINITY);
+    assert_eq!(pid.step(5.0), 0.0);
+    pid.set_kp(2.0).unwrap();
+    assert_eq!(pid.step(1e6), 2e6);
+}
+
+#[test]
+fn try_step_rejects_nan_and_keeps_state() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_gains(1.0, 1.0, 1.0).unwrap();
+    pid.step(1.0);
+    let state = pid.state();
+    assert_eq!(pid.try_step(f64::NAN), Err(PidError::NonFinite));
+    assert_eq!(pid.try_step(f64::INFINITY), Err(PidError::NonFinite));
+    assert_eq!(pid.state(), state);
+    assert!(pid.try_step(1.0).is_ok());
+}
+
+#[test]
+fn update_matches_step_on_the_error() {
+    let mut by_update = pid(0.1, (-5.0, 5.0));
+    by_update.set_gains(1.0, 0.5, 0.2).unwrap();
+    by_update.set_smooth(0.6).unwrap();
+    let mut by_error = by_update;
+    by_update.set_setpoint(3.0).unwrap();
+    for k in 0..100 {
+        let y = 2.0 * (k as f64 * 0.1).sin();
+        assert_eq!(by_update.update(y), by_error.step(3.0 - y));
+    }
+}
+
+#[test]
+fn narrowing_the_clamp_at_runtime() {
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    pid.set_kp(1.0).unwrap();
+    assert_eq!(pid.step(0.8), 0.8);
+    assert!(pid.unclamped());
+
+    pid.set_clamp((-0.5, 0.5)).unwrap();
+    assert_eq!(pid.saturation(), Saturation::Upper);
+    assert_eq!(pid.step(0.8), 0.5);
+    assert_eq!(pid.step(-0.8), -0.5);
+    assert_eq!(pid.set_clamp((0.5, -0.5)), Err(PidError::InvertedClamp));
+    assert_eq!(pid.clamp_hi(), 0.5);
+}
+
+#[test]
+fn narrowing_the_clamp_under_a_rate_limit() {
+    let mut pid = pid(0.1, (-20.0, 20.0));
+    pid.set_kp(1.0).unwrap();
+    pid.set_output_rate_limit(5.0).unwrap();
+    for _ in 0..30 {
+        pid.step(10.0);
+    }
+    assert_eq!(pid.step(10.0), 10.0);
+
+    pid.set_clamp((-1.0, 1.0)).unwrap();
+    // the rate limit continues from the last output moved into the range
+    assert_close(pid.step(-10.0), 0.5, 1e-12);
+    assert_eq!(pid.step(10.0), 1.0);
+    assert_close(pid.step(-10.0), 0.5, 1e-12);
+    assert_close(pid.step(-10.0), 0.0, 1e-12);
+    assert_close(pid.step(-10.0), -0.5, 1e-12);
+    assert_eq!(pid.step(-10.0), -1.0);
+}
+
+#[test]
+fn integral_limits_bound_the_contribution() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_ki(2.0).unwrap();
+    assert_eq!(pid.integral_limits(), None);
+    pid.set_integral_limits(-1.0, 0.5).unwrap();
+    assert_eq!(pid.integral_limits(), Some((-1.0, 0.5)));
+    for _ in 0..100 {
+        pid.step(1.0);
+    }
+    assert_eq!(pid.i_contribution(), 0.5);
+    assert_eq!(pid.i(), 0.25);
+    for _ in 0..100 {
+        pid.step(-1.0);
+    }
+    assert_eq!(pid.i_contribution(), -1.0);
+
+    // the limit is on ki * i, so it holds across a change of ki
+    pid.set_ki(4.0).unwrap();
+    pid.step(-1.0);
+    assert_eq!(pid.i_contribution(), -1.0);
+    assert_eq!(
+        pid.set_integral_limits(1.0, -1.0),
+        Err(PidError::InvertedClamp)
+    );
+}
+
+#[test]
+fn integral_is_not_bounded_by_the_clamp_range() {
+    // a bias of 50 in [0, 100] needs a negative integral to reach 0
+    let mut pid = pid(0.1, (0.0, 100.0));
+    pid.set_ki(1.0).unwrap();
+    pid.set_output_bias(50.0).unwrap();
+    let mut u = 50.0;
+    for _ in 0..200 {
+        let next = pid.step(-10.0);
+        assert!(next <= u);
+        u = next;
+    }
+    assert_eq!(u, 0.0);
+    assert!(pid.i_contribution() < -40.0);
+
+    // the same for a feedforward that alone exceeds the clamp range
+    let mut pid = self::pid(0.1, (0.0, 100.0));
+    pid.set_ki(1.0).unwrap();
+    for _ in 0..200 {
+        pid.step_ff(-10.0, 150.0);
+    }
+    assert!(pid.step_ff(-10.0, 150.0) < 100.0);
+    assert!(pid.i_contribution() < -50.0);
+}
+
+#[test]
+fn input_filter_calms_the_output() {
+    let total_variation = |alpha: f64| {
+        let mut pid = pid(0.01, (-10.0, 10.0));
+        pid.set_kp(1.0).unwrap();
+        pid.set_input_filter(alpha).unwrap();
+        let mut noise = Noise(3);
+        let mut last = 0.0;
+        let mut variation = 0.0;
+        for k in 0..2000 {
+            let square = if (k / 200) % 2 == 0 { 1.0 } else { -1.0 };
+            let u = pid.step(square + 0.2 * noise.next());
+            variation += (u - last).abs();
+            last = u;
+        }
+        variation
+    };
+    assert!(total_variation(0.1) < total_variation(1.0) / 3.0);
+}
+
+#[test]
+fn input_filter_follows_a_constant_error() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_kp(1.0).unwrap();
+    pid.set_input_filter(0.5).unwrap();
+    assert_eq!(pid.step(1.0), 0.5);
+    assert_eq!(pid.step(1.0), 0.75);
+    for _ in 0..60 {
+        pid.step(1.0);
+    }
+    assert_close(pid.step(1.0), 1.0, 1e-12);
+}
+
+#[test]
+fn new_symmetric_clamps_both_sides() {
+    let mut pid = PidController::<f64>::new_symmetric(0.1, 2.0);
+    assert_eq!((pid.clamp_lo(), pid.clamp_hi()), (-2.0, 2.0));
+    pid.set_kp(1.0).unwrap();
+    assert_eq!(pid.step(5.0), 2.0);
+    assert_eq!(pid.step(-5.0), -2.0);
+}
+
+#[test]
+#[should_panic(expected = "half_range must be positive")]
+fn new_symmetric_rejects_a_zero_range() {
+    PidController::<f64>::new_symmetric(0.1, 0.0);
+}
+
+#[test]
+fn deadband_still_corrects_a_real_disturbance() {
+    let dt = 0.01;
+    let mut pid = pid(dt, (-10.0, 10.0));
+    pid.set_gains(2.0, 1.0, 0.0).unwrap();
+    pid.set_deadband(0.05).unwrap();
+    let mut y = 1.0;
+    for k in 0..3000 {
+        let disturbance = if k >= 500 { -1.0 } else { 0.0 };
+        let u = pid.step_with_setpoint(1.0, y);
+        y = lag(y, u + disturbance, 0.5, dt);
+    }
+    // settles at the band edge, where I stops
+    assert!((y - 1.0).abs() <= 0.06, "{}", y);
+    assert!(pid.i_contribution() > 0.8);
+}
+
+#[test]
+fn deadband_edge_is_continuous() {
+    // the error crosses the band edge at 0.1 in small steps, with D and a
+    // setpoint weight on P
+    let mut pid = pid(0.01, (-100.0, 100.0));
+    pid.set_gains(2.0, 0.0, 0.05).unwrap();
+    pid.set_b(0.5).unwrap();
+    pid.set_deadband(0.1).unwrap();
+    pid.step_with_setpoint(1.0, 0.8);
+    let mut last = pid.step_with_setpoint(1.0, 0.8);
+    for k in 1..=400 {
+        let y = 0.8 + k as f64 * 0.0005;
+        let u = pid.step_with_setpoint(1.0, y);
+        assert!((u - last).abs() < 0.01, "jump of {} at {}", u - last, y);
+        last = u;
+    }
+    // inside the band D still damps the motion
+    assert_close(pid.d(), -0.05, 1e-9);
+}
+
+#[test]
+fn integral_leak_decays_with_its_time_constant() {
+    // a disturbance for one second at 1 kHz, then none
+    let dt = 0.001;
+    let mut pid = pid(dt, (-10.0, 10.0));
+    pid.set_ki(1.0).unwrap();
+    pid.set_integral_leak(0.5).unwrap();
+    assert_eq!(pid.leak(), 2.0);
+    for _ in 0..1000 {
+        pid.step(1.0);
+    }
+    let peak = pid.i();
+    for k in 1..=1000 {
+        pid.step(0.0);
+        let t = k as f64 * dt;
+        assert_close(pid.i(), peak * (-t / 0.5).exp(), 1e-12);
+    }
+
+    pid.set_integral_leak(f64::INFINITY).unwrap();
+    assert_eq!(pid.leak(), 0.0);
+    assert_eq!(
+        pid.set_integral_leak(0.0),
+        Err(PidError::NonPositiveTimeConstant)
+    );
+}
+
+#[test]
+fn derivative_filter_cutoff_matches_its_time_constant() {
+    let mut by_cutoff = pid(0.01, (-10.0, 10.0));
+    by_cutoff.set_derivative_filter_cutoff(20.0).unwrap();
+    let mut by_tau = pid(0.01, (-10.0, 10.0));
+    by_tau.set_derivative_tau(0.05).unwrap();
+    assert_close(by_cutoff.smooth(), by_tau.smooth(), 1e-15);
+    assert_close(by_cutoff.smooth(), 0.2 / 1.2, 1e-15);
+
+    by_cutoff
+        .set_derivative_filter_cutoff(f64::INFINITY)
+        .unwrap();
+    assert_eq!(by_cutoff.smooth(), 1.0);
+    assert_eq!(
+        by_cutoff.set_derivative_filter_cutoff(0.0),
+        Err(PidError::NonPositiveTimeConstant)
+    );
+}
+
+#[test]
+fn every_anti_windup_variant_under_saturation() {
+    let run = |anti_windup| {
+        let mut pid = pid(0.1, (-1.0, 1.0));
+        pid.set_gains(1.0, 1.0, 0.0).unwrap();
+        pid.set_anti_windup(anti_windup).unwrap();
+        for _ in 0..10 {
+            assert_eq!(pid.step(5.0), 1.0);
+        }
+        pid.i()
+    };
+    let clamp = run(AntiWindup::Clamp);
+    let conditional = run(AntiWindup::ConditionalIntegration);
+    let back_calculation = run(AntiWindup::BackCalculation { kaw: 0.5 });
+    let none = run(AntiWindup::None);
+
+    // Clamp and ConditionalIntegration only integrate the first step
+    assert_close(clamp, 0.5, 1e-15);
+    assert_close(conditional, 0.5, 1e-15);
+    assert_close(none, 5.0, 1e-12);
+    assert!(back_calculation < none && back_calculation > clamp);
+}
+
+#[test]
+fn back_calculation_recovers_faster_than_no_anti_windup() {
+    let back_calculation = recovery_after_saturation(AntiWindup::BackCalculation { kaw: 1.0 });
+    let none = recovery_after_saturation(AntiWindup::None);
+    assert!(
+        back_calculation.0 < none.0 / 2.0,
+        "{:?} vs {:?}",
+        back_calculation,
+        none
+    );
+    assert!(
+        back_calculation.1 < none.1,
+        "{:?} vs {:?}",
+        back_calculation,
+        none
+    );
+}
+
+#[test]
+fn manual_and_back_to_auto_is_continuous() {
+    let dt = 0.01;
+    let mut pid = pid(dt, (-10.0, 10.0));
+    pid.set_gains(1.0, 1.0, 0.1).unwrap();
+    pid.set_derivative_mode(DerivativeMode::OnMeasurement);
+    let mut y = 0.0;
+    let mut u_last = pid.step_with_setpoint(1.0, y);
+    // largest change between two steps in auto, after the initial step
+    let mut largest_change: f64 = 0.0;
+    for _ in 0..1000 {
+        y = lag(y, u_last, 1.0, dt);
+        let u = pid.step_with_setpoint(1.0, y);
+        largest_change = largest_change.max((u - u_last).abs());
+        u_last = u;
+    }
+
+    let manual = u_last + 0.3;
+    pid.set_manual_output(manual).unwrap();
+    for _ in 0..200 {
+        assert_eq!(pid.step_with_setpoint(1.0, y), manual);
+        y = lag(y, manual, 1.0, dt);
+    }
+
+    pid.set_auto();
+    assert_eq!(pid.mode(), Mode::Auto);
+    let u = pid.step_with_setpoint(1.0, y);
+    assert!(
+        (u - manual).abs() <= largest_change,
+        "{} after {}",
+        u,
+        manual
+    );
+}
+
+#[test]
+fn derivative_window_averages_the_change() {
+    let mut pid = pid(0.1, (-1e3, 1e3));
+    pid.set_kd(1.0).unwrap();
+    pid.set_derivative_window(4).unwrap();
+    let errors = [0.0, 1.0, 1.0, 2.0, 2.0, 3.0];
+    for &e in &errors {
+        pid.step(e);
+    }
+    // (e[n] - e[n-4]) / (4 dt)
+    assert_close(pid.d(), (3.0 - 1.0) / 0.4, 1e-12);
+    assert_eq!(
+        pid.set_derivative_window(0),
+        Err(PidError::WindowOutOfRange)
+    );
+}
+
+#[test]
+fn setpoint_ramp_moves_linearly_without_kick() {
+    let mut pid = pid(0.1, (-100.0, 100.0));
+    pid.set_kd(1.0).unwrap();
+    pid.set_setpoint_ramp(1.0).unwrap();
+    pid.set_setpoint(10.0).unwrap();
+    for k in 1..=50 {
+        pid.update(0.0);
+        assert_close(pid.effective_setpoint(), 0.1 * k as f64, 1e-9);
+        assert!(pid.kd() * pid.d() <= 1.0 + 1e-9);
+    }
+
+    // a new target mid-ramp turns around from the current value
+    pid.set_setpoint(0.0).unwrap();
+    pid.update(0.0);
+    assert_close(pid.effective_setpoint(), 4.9, 1e-9);
+}
+
+#[test]
+fn setpoint_ramp_rates_up_and_down() {
+    let mut pid = pid(0.1, (-100.0, 100.0));
+    pid.set_setpoint_ramp_rates(2.0, 0.5).unwrap();
+    pid.set_setpoint(1.0).unwrap();
+    pid.update(0.0);
+    assert_close(pid.effective_setpoint(), 0.2, 1e-12);
+    pid.set_setpoint(-1.0).unwrap();
+    pid.update(0.0);
+    assert_close(pid.effective_setpoint(), 0.15, 1e-12);
+    assert_eq!(pid.setpoint_ramp_rates(), (2.0, 0.5));
+}
+
+#[test]
+fn set_integral_preloads_the_integrator() {
+    let mut pid = pid(0.1, (-10.0, 10.0));
+    pid.set_gains(5.0, 2.0, 1.0).unwrap();
+    pid.set_integral(1.5).unwrap();
+    assert_eq!(pid.i(), 1.5);
+    assert_eq!(pid.step(0.0), 3.0);
+    assert_eq!(pid.set_integral(f64::NAN), Err(PidError::NaN));
+}
+
+#[test]
+fn nan_mid_sequence_is_skipped() {
+    let errors: Vec<f64> = (0..100).map(|k| (k as f64 * 0.2).sin()).collect();
+    for policy in [NanPolicy::Propagate, NanPolicy::HoldLastOutput] {
+        let mut glitched = pid(0.1, (-2.0, 2.0));
+        glitched.set_gains(1.0, 0.5, 0.1).unwrap();
+        glitched.set_smooth(0.5).unwrap();
+        glitched.set_nan_policy(policy);
+        let mut clean = glitched;
+
+        for (k, &e) in errors.iter().enumerate() {
+            if k == 50 {
+                let u_last = glitched.state().u_prev;
+                let u = glitched.step(f64::NAN);
+                match policy {
+                    NanPolicy::Propagate => assert!(u.is_nan()),
+                    _ => assert_eq!(u, u_last),
+                }
+                assert!(glitched.i().is_finite() && glitched.d().is_finite());
+            }
+            assert_eq!(glitched.step(e), clean.step(e));
+        }
+    }
+}
+
+#[test]
+fn reset_and_hold_restarts_after_nan() {
+    let mut pid = pid(0.1, (-2.0, 2.0));
+    pid.set_gains(1.0, 0.5, 0.0).unwrap();
+    pid.set_nan_policy(NanPolicy::ResetAndHold);
+    for _ in 0..10 {
+        pid.step(1.0);
+    }
+    let u = pid.state().u_prev;
+    assert_eq!(pid.step(f64::NAN), u);
+    assert_eq!(pid.i(), 0.0);
+    assert_eq!(pid.step(1.0), 1.0 + 0.5 * 0.1);
+}
+
+#[test]
+fn non_finite_dt_is_handled_like_non_finite_input() {
+    let mut pid = pid(0.1, (-2.0, 2.0));
+    pid.set_gains(1.0, 0.5, 0.0).unwrap();
+    pid.set_nan_policy(NanPolicy::HoldLastOutput);
+    let u = pid.step(1.0);
+    let state = pid.state();
+    assert_eq!(pid.step_with_dt(1.0, f64::NAN), u);
+    assert_eq!(pid.step_with_dt(1.0, f64::INFINITY), u);
+    assert_eq!(pid.state(), state);
+}
+
+#[test]
+fn prevent_derivative_kick_zeroes_the_term() {
+    for window in [1, 3] {
+        let mut pid = pid(0.1, (-1000.0, 1000.0));
+        pid.set_kd(1.0).unwrap();
+        pid.set_smooth(0.5).unwrap();
+        pid.set_derivative_window(window).unwrap();
+        pid.set_prevent_derivative_kick(true);
+        for k in 0..10 {
+            pid.step_with_setpoint(0.0, -0.1 * k as f64);
+        }
+        assert!(pid.d_contribution() > 0.0);
+
+        assert_eq!(pid.step_with_setpoint(10.0, -1.0), 0.0);
+        assert_eq!(pid.d_contribution(), 0.0);
+        // from the next step the derivative follows the measurement again
+        pid.step_with_setpoint(10.0, -1.1);
+        assert!(pid.d_contribution() > 0.0);
+    }
+}
+
+#[test]
+fn prevent_derivative_kick_covers_the_first_setpoint() {
+    let mut pid = pid(0.1, (-1000.0, 1000.0));
+    pid.set_kd(1.0).unwrap();
+    pid.set_prevent_derivative_kick(true);
+    // the zeroed history would give a derivative of -50 / 0.1
+    assert_eq!(pid.step_with_setpoint(0.0, 50.0), 0.0);
+    assert_eq!(pid.step_with_setpoint(0.0, 50.0), 0.0);
+    assert_eq!(pid.step_with_setpoint(0.0, 50.5), -5.0);
+}
+
+#[test]
+fn trapezoidal_integrates_a_sine_more_accurately() {
+    let error = |method: Integration| {
+        let dt = 0.2;
+        let mut pid = pid(dt, (-1e9, 1e9));
+        pid.set_ki(1.0).unwrap();
+        pid.set_integration_method(method);
+        let steps = 30;
+        for k in 0..=steps {
+            pid.step((k as f64 * dt).sin());
+        }
+        let t = steps as f64 * dt;
+        (pid.i() - (1.0 - t.cos())).abs()
+    };
+    let euler = error(Integration::ForwardEuler);
+    let trapezoidal = error(Integration::Trapezoidal);
+    assert!(trapezoidal < euler / 10.0, "{} vs {}", trapezoidal, euler);
+}
+
+#[test]
+fn trapezoidal_integrates_a_ramp_exactly() {
+    let integral = |method: Integration| {
+        let mut pid = pid(0.1, (-1e9, 1e9));
+        pid.set_ki(1.0).unwrap();
+        pid.set_integration_method(method);
+        for k in 0..=10 {
+            pid.step(k as f64 * 0.1);
+        }
+        pid.i()
+    };
+    // integral of t over [0, 1] is 0.5
+    assert_close(integral(Integration::Trapezoidal), 0.5, 1e-12);
+    assert_close(integral(Integration::ForwardEuler), 0.55, 1e-12);
+}
+
+#[test]
+fn derivative_filter_rolls_off_at_n() {
+    // kd * s / (1 + (kd / n) * s): gain kd * w at low and n at high frequency
+    let amplitude = |w: f64| {
+        let dt = 1e-4;
+        let mut pid = pid(dt, (-1e3, 1e3));
+        pid.set_kd(1.0).unwrap();
+        pid.set_derivative_filter(10.0).unwrap();
+        let period = (2.0 * core::f64::consts::PI / w / dt) as usize;
+        let settle = (1.0 / dt) as usize + period;
+        let mut peak: f64 = 0.0;
+        for k in 0..settle + period {
+            let u = pid.step((w * k as f64 * dt).sin());
+            if k >= settle {
+                peak = peak.max(u.abs());
+            }
+        }
+        peak
+    };
+    let expected = |w: f64| w / (1.0 + (w / 10.0).powi(2)).sqrt();
+    for w in [1.0, 10.0, 200.0] {
+        let a = amplitude(w);
+        assert_close(a, expected(w), 0.03 * expected(w));
+    }
+    assert!(amplitude(1000.0) < 10.0);
+}
+
+#[test]
+fn saturation_count_tracks_saturated_steps() {
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    pid.set_kp(1.0).unwrap();
+    for k in 1..=20 {
+        pid.step(2.0);
+        assert!(pid.is_saturated());
+        assert_eq!(pid.saturation_count(), k);
+    }
+    pid.step(0.5);
+    assert!(!pid.is_saturated());
+    assert_eq!(pid.saturation_count(), 20);
+    pid.step(-3.0);
+    assert_eq!(pid.saturation_count(), 21);
+    pid.reset();
+    assert_eq!(pid.saturation_count(), 0);
+}
+
+#[test]
+fn invalid_applied_gains_change_nothing() {
+    let mut pid = pid(0.1, (-1.0, 1.0));
+    pid.set_gains(1.0, 2.0, 3.0).unwrap();
+    let gains = pid.gains();
+    assert_eq!(
+        pid.apply_gains(PidGains {
+            kp: 4.0,
+            ki: -1.0,
+            kd: 0.0
+        }),
+        Err(PidError::NegativeGain)
+    );
+    assert_eq!(pid.gains(), gains);
+}
+
+#[test]
+fn from_duration_sets_dt() {
+    let pid = PidController::<f64>::from_duration(Duration::from_millis(10), (-1.0, 1.0)).unwrap();
+    assert_eq!(pid.dt(), 0.01);
+    assert_eq!(
+        PidController::<f64>::from_duration(Duration::ZERO, (-1.0, 1.0)),
+        Err(PidError::NonPositiveDt)
+    );
+}
@@ -0,0 +1,113 @@
+use crate::{check_gain, check_time_constant, PidController, PidError};
+
+use num_traits::Float;
+
+/// Ziegler-Nichols style tuning rules based on the ultimate gain ku and the
+/// period tu of the sustained oscillation at that gain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TuningRule {
+    /// kp = 0.6 ku, ti = tu / 2, td = tu / 8
+    ClassicPid,
+
+    /// kp = 0.7 ku, ti = 0.4 tu, td = 0.15 tu
+    PessenIntegral,
+
+    /// kp = ku / 3, ti = tu / 2, td = tu / 3
+    SomeOvershoot,
+
+    /// kp = 0.2 ku, ti = tu / 2, td = tu / 3
+    NoOvershoot,
+
+    /// kp = ku / 2.2, ti = 2.2 tu, td = tu / 6.3.
+    /// more conservative than ClassicPid, less overshoot.
+    TyreusLuyben,
+}
+
+impl TuningRule {
+    /// (kp, ti, td) in the standard form, see set_standard_gains()
+    pub fn standard_gains<T: Float>(self, ku: T, tu: T) -> Result<(T, T, T), PidError> {
+        let ku = check_gain(ku)?;
+        let tu = check_time_constant(tu)?;
+        let c = |x: f64| T::from(x).unwrap();
+        let (kp, ti, td) = match self {
+            TuningRule::ClassicPid => (c(0.6), c(0.5), c(0.125)),
+            TuningRule::PessenIntegral => (c(0.7), c(0.4), c(0.15)),
+            TuningRule::SomeOvershoot => (c(1.0 / 3.0), c(0.5), c(1.0 / 3.0)),
+            TuningRule::NoOvershoot => (c(0.2), c(0.5), c(1.0 / 3.0)),
+            TuningRule::TyreusLuyben => (c(1.0 / 2.2), c(2.2), c(1.0 / 6.3)),
+        };
+        Ok((kp * ku, ti * tu, td * tu))
+    }
+}
+
+impl<T: Float> PidController<T> {
+    /// try_new() with gains from a Ziegler-Nichols style rule.
+    /// ti and td are converted as in set_standard_gains().
+    ///
+    /// ```
+    /// use pid::{PidController, TuningRule};
+    ///
+    /// let pid = PidController::<f64>::ziegler_nichols(0.1, (-1.0, 1.0), 2.0, 4.0, TuningRule::ClassicPid)
+    ///     .unwrap();
+    /// assert!((pid.kp() - 1.2).abs() < 1e-12);
+    /// assert!((pid.ki() - 0.6).abs() < 1e-12); // 1.2 / (4.0 / 2)
+    /// assert!((pid.kd() - 0.6).abs() < 1e-12); // 1.2 * (4.0 / 8)
+    /// ```
+    pub fn ziegler_nichols(
+        dt: T,
+        clamp: (T, T),
+        ku: T,
+        tu: T,
+        rule: TuningRule,
+    ) -> Result<Self, PidError> {
+        let (kp, ti, td) = rule.standard_gains(ku, tu)?;
+        let mut pid = Self::try_new(dt, clamp)?;
+        pid.set_standard_gains(kp, ti, td)?;
+        Ok(pid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-12, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn every_rule_matches_its_table() {
+        let (ku, tu) = (3.0, 2.0);
+        let table = [
+            (TuningRule::ClassicPid, (1.8, 1.0, 0.25)),
+            (TuningRule::PessenIntegral, (2.1, 0.8, 0.3)),
+            (TuningRule::SomeOvershoot, (1.0, 1.0, 2.0 / 3.0)),
+            (TuningRule::NoOvershoot, (0.6, 1.0, 2.0 / 3.0)),
+            (TuningRule::TyreusLuyben, (3.0 / 2.2, 4.4, 2.0 / 6.3)),
+        ];
+        for (rule, (kp, ti, td)) in table {
+            let gains = rule.standard_gains(ku, tu).unwrap();
+            assert_close(gains.0, kp);
+            assert_close(gains.1, ti);
+            assert_close(gains.2, td);
+
+            let pid =
+                PidController::<f64>::ziegler_nichols(0.1, (-1.0, 1.0), ku, tu, rule).unwrap();
+            assert_close(pid.kp(), kp);
+            assert_close(pid.ki(), kp / ti);
+            assert_close(pid.kd(), kp * td);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_ultimate_gain_and_period() {
+        let rule = TuningRule::ClassicPid;
+        assert_eq!(rule.standard_gains(-1.0, 2.0), Err(PidError::NegativeGain));
+        assert_eq!(rule.standard_gains(f64::NAN, 2.0), Err(PidError::NaN));
+        assert_eq!(
+            rule.standard_gains(1.0, 0.0),
+            Err(PidError::NonPositiveTimeConstant)
+        );
+    }
+}